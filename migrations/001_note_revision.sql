@@ -0,0 +1,17 @@
+-- Snapshots of a note taken before every update, used for history / diff / restore
+CREATE TABLE IF NOT EXISTS note_revision (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    note_id INT NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    content LONGTEXT NOT NULL,
+    description TEXT NULL,
+    cover VARCHAR(255) NULL,
+    is_top INT NULL,
+    status VARCHAR(32) NULL,
+    tags TEXT NULL,
+    category_id INT NULL,
+    note_updated_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL,
+    INDEX idx_note_revision_note (note_id),
+    CONSTRAINT fk_note_revision_note FOREIGN KEY (note_id) REFERENCES note (id) ON DELETE CASCADE
+) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
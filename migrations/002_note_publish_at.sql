@@ -0,0 +1,3 @@
+-- Scheduled publishing: notes with status 'scheduled' go live once publish_at has passed
+ALTER TABLE note ADD COLUMN publish_at DATETIME NULL;
+CREATE INDEX idx_note_status_publish_at ON note (status, publish_at);
@@ -0,0 +1,12 @@
+-- Human-readable permalinks; old slugs keep resolving after a rename
+ALTER TABLE note ADD COLUMN slug VARCHAR(191) NULL;
+CREATE UNIQUE INDEX uk_note_slug ON note (slug);
+
+CREATE TABLE IF NOT EXISTS note_slug_redirect (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    slug VARCHAR(191) NOT NULL,
+    note_id INT NOT NULL,
+    created_at DATETIME NOT NULL,
+    UNIQUE KEY uk_note_slug_redirect_slug (slug),
+    CONSTRAINT fk_note_slug_redirect_note FOREIGN KEY (note_id) REFERENCES note (id) ON DELETE CASCADE
+) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
@@ -0,0 +1,35 @@
+-- Notes <-> tags as a relation instead of the free-text note.tags column.
+-- level 1 points at tag_one.id, level 2 at tag_two.id.
+CREATE TABLE IF NOT EXISTS note_tag (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    note_id INT NOT NULL,
+    tag_id INT NOT NULL,
+    level INT NOT NULL,
+    UNIQUE KEY uk_note_tag (note_id, level, tag_id),
+    INDEX idx_note_tag_tag (level, tag_id),
+    CONSTRAINT fk_note_tag_note FOREIGN KEY (note_id) REFERENCES note (id) ON DELETE CASCADE
+) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
+
+-- Backfill from the comma separated ids in note.tags. Those ids carry no level, so an
+-- id present in both tag_one and tag_two is not guessed at: it is left out here and
+-- listed by the query at the end, to be re-tagged by hand (or saved again with tagIds).
+INSERT IGNORE INTO note_tag (note_id, tag_id, level)
+SELECT n.id, t.id, 1
+FROM note n
+JOIN tag_one t ON FIND_IN_SET(t.id, REPLACE(n.tags, ' ', '')) > 0
+WHERE n.tags IS NOT NULL AND n.tags <> ''
+  AND NOT EXISTS (SELECT 1 FROM tag_two w WHERE w.id = t.id);
+
+INSERT IGNORE INTO note_tag (note_id, tag_id, level)
+SELECT n.id, t.id, 2
+FROM note n
+JOIN tag_two t ON FIND_IN_SET(t.id, REPLACE(n.tags, ' ', '')) > 0
+WHERE n.tags IS NOT NULL AND n.tags <> ''
+  AND NOT EXISTS (SELECT 1 FROM tag_one o WHERE o.id = t.id);
+
+-- Ambiguous ids that were skipped above
+SELECT n.id AS note_id, n.title, o.id AS tag_id, o.name AS tag_one_name, w.name AS tag_two_name
+FROM note n
+JOIN tag_one o ON FIND_IN_SET(o.id, REPLACE(n.tags, ' ', '')) > 0
+JOIN tag_two w ON w.id = o.id
+WHERE n.tags IS NOT NULL AND n.tags <> '';
@@ -0,0 +1,20 @@
+-- Multi-part article collections. A note belongs to at most one series.
+CREATE TABLE IF NOT EXISTS series (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    title VARCHAR(255) NOT NULL,
+    description TEXT NULL,
+    cover VARCHAR(255) NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
+
+CREATE TABLE IF NOT EXISTS series_note (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    series_id INT NOT NULL,
+    note_id INT NOT NULL,
+    position INT NOT NULL,
+    UNIQUE KEY uk_series_note_note (note_id),
+    INDEX idx_series_note_series (series_id, position),
+    CONSTRAINT fk_series_note_series FOREIGN KEY (series_id) REFERENCES series (id) ON DELETE CASCADE,
+    CONSTRAINT fk_series_note_note FOREIGN KEY (note_id) REFERENCES note (id) ON DELETE CASCADE
+) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
@@ -0,0 +1,15 @@
+-- Page views. Visitors are only stored as a daily-salted hash of IP + User-Agent;
+-- a visitor counts once per note and day, `hits` keeps the raw page views.
+CREATE TABLE IF NOT EXISTS note_view (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    note_id INT NOT NULL,
+    visitor_hash CHAR(64) NOT NULL,
+    view_date DATE NOT NULL,
+    hits INT NOT NULL DEFAULT 1,
+    UNIQUE KEY uk_note_view (note_id, visitor_hash, view_date),
+    INDEX idx_note_view_date (view_date),
+    CONSTRAINT fk_note_view_note FOREIGN KEY (note_id) REFERENCES note (id) ON DELETE CASCADE
+) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
+
+-- Denormalised total shown with every note
+ALTER TABLE note ADD COLUMN view_count INT NOT NULL DEFAULT 0;
@@ -0,0 +1,10 @@
+-- Anonymous emoji reactions on notes and talks, one per visitor, target and emoji.
+CREATE TABLE IF NOT EXISTS reaction (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    target_type VARCHAR(16) NOT NULL,
+    target_id INT NOT NULL,
+    emoji VARCHAR(16) NOT NULL,
+    visitor_hash CHAR(64) NOT NULL,
+    created_at DATETIME NOT NULL,
+    UNIQUE KEY uk_reaction (target_type, target_id, emoji, visitor_hash)
+) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
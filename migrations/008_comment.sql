@@ -0,0 +1,20 @@
+-- Threaded comments on notes and talks with a moderation state.
+-- Deleting a comment deletes its replies.
+CREATE TABLE IF NOT EXISTS comment (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    target_type VARCHAR(16) NOT NULL,
+    target_id INT NOT NULL,
+    parent_id INT NULL,
+    author_name VARCHAR(64) NOT NULL,
+    author_email VARCHAR(255) NOT NULL,
+    author_website VARCHAR(255) NULL,
+    content TEXT NOT NULL,
+    content_html TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    is_admin TINYINT(1) NOT NULL DEFAULT 0,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    INDEX idx_comment_target (target_type, target_id, status),
+    INDEX idx_comment_status (status, created_at),
+    CONSTRAINT fk_comment_parent FOREIGN KEY (parent_id) REFERENCES comment (id) ON DELETE CASCADE
+) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
@@ -0,0 +1,9 @@
+-- Local naive Bayes spam classifier, trained when comments are approved or marked as spam.
+CREATE TABLE IF NOT EXISTS spam_token (
+    token VARCHAR(64) NOT NULL PRIMARY KEY,
+    spam INT NOT NULL DEFAULT 0,
+    ham INT NOT NULL DEFAULT 0
+) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin;
+
+-- Which label a comment was last trained as, so a changed decision can be unlearned
+ALTER TABLE comment ADD COLUMN trained_as VARCHAR(8) NULL;
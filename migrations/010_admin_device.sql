@@ -0,0 +1,8 @@
+-- Browsers the admin has logged in from, used for new-device login emails.
+CREATE TABLE IF NOT EXISTS admin_device (
+    device_hash CHAR(64) NOT NULL PRIMARY KEY,
+    user_agent VARCHAR(512) NOT NULL,
+    last_ip VARCHAR(64) NOT NULL,
+    first_seen DATETIME NOT NULL,
+    last_seen DATETIME NOT NULL
+) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
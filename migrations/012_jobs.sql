@@ -0,0 +1,20 @@
+-- Persistent background jobs run by workers inside the server process.
+CREATE TABLE IF NOT EXISTS job (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    job_type VARCHAR(64) NOT NULL,
+    payload MEDIUMTEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'queued',
+    attempts INT NOT NULL DEFAULT 0,
+    max_attempts INT NOT NULL DEFAULT 5,
+    unique_key VARCHAR(191) NULL,
+    last_error TEXT NULL,
+    run_at DATETIME NOT NULL,
+    locked_by CHAR(32) NULL,
+    locked_at DATETIME NULL,
+    finished_at DATETIME NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    UNIQUE KEY uk_job_unique_key (unique_key),
+    INDEX idx_job_due (status, run_at),
+    INDEX idx_job_type (job_type, status)
+) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_unicode_ci;
@@ -0,0 +1,4 @@
+-- Nested categories: an optional parent and a position among siblings
+ALTER TABLE category ADD COLUMN parent_id INT NULL;
+ALTER TABLE category ADD COLUMN sort_order INT NOT NULL DEFAULT 0;
+CREATE INDEX idx_category_parent ON category (parent_id, sort_order);
@@ -0,0 +1,126 @@
+use axum::http::HeaderMap;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Header carrying a solved challenge: `<challenge>:<counter>`.
+pub const CAPTCHA_HEADER: &str = "x-captcha";
+
+// ~260k hashes on average, well under a second in a browser worker
+const DEFAULT_DIFFICULTY: u32 = 18;
+const CHALLENGE_TTL_SECS: i64 = 300;
+
+#[derive(Serialize, Default)]
+pub struct ChallengeDto {
+    pub challenge: String,
+    // Required number of leading zero bits in sha256("<challenge>:<counter>")
+    pub difficulty: u32,
+    #[serde(rename = "expiresIn")]
+    pub expires_in: i64,
+}
+
+/// Self-hosted hashcash-style proof of work. Challenges are signed rather than
+/// stored; only solved ones are remembered, until they expire, so each is used once.
+pub struct Captcha {
+    secret: String,
+    difficulty: u32,
+    used: Mutex<HashMap<String, i64>>,
+}
+
+impl Default for Captcha {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIFFICULTY)
+    }
+}
+
+fn now_secs() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            return bits + byte.leading_zeros();
+        }
+    }
+    bits
+}
+
+impl Captcha {
+    pub fn new(difficulty: u32) -> Self {
+        let secret = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        Self { secret, difficulty, used: Mutex::new(HashMap::new()) }
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    fn sign(&self, payload: &str) -> String {
+        hex::encode(self.mac(payload).finalize().into_bytes())
+    }
+
+    /// Checks a hex signature in constant time.
+    fn signed(&self, payload: &str, signature: &str) -> bool {
+        hex::decode(signature).is_ok_and(|sig| self.mac(payload).verify_slice(&sig).is_ok())
+    }
+
+    pub fn challenge(&self) -> ChallengeDto {
+        let payload = format!("{}.{}.{}", now_secs(), self.difficulty, uuid::Uuid::new_v4().simple());
+        ChallengeDto {
+            challenge: format!("{}.{}", payload, self.sign(&payload)),
+            difficulty: self.difficulty,
+            expires_in: CHALLENGE_TTL_SECS,
+        }
+    }
+
+    /// Brute-forces a challenge the way the frontend does; meant for tests and tooling.
+    pub fn solve(challenge: &str, difficulty: u32) -> String {
+        (0u64..)
+            .map(|counter| format!("{}:{}", challenge, counter))
+            .find(|s| leading_zero_bits(&Sha256::digest(s.as_bytes())) >= difficulty)
+            .expect("a solution exists")
+    }
+
+    /// Checks a `<challenge>:<counter>` solution and burns the challenge.
+    pub fn verify(&self, solution: &str) -> Result<(), &'static str> {
+        let (challenge, _) = solution.rsplit_once(':').ok_or("Invalid captcha")?;
+        let (payload, signature) = challenge.rsplit_once('.').ok_or("Invalid captcha")?;
+        if !self.signed(payload, signature) {
+            return Err("Invalid captcha");
+        }
+
+        let mut parts = payload.split('.');
+        let issued: i64 = parts.next().and_then(|p| p.parse().ok()).ok_or("Invalid captcha")?;
+        let difficulty: u32 = parts.next().and_then(|p| p.parse().ok()).ok_or("Invalid captcha")?;
+        let now = now_secs();
+        if now - issued > CHALLENGE_TTL_SECS {
+            return Err("Captcha expired");
+        }
+        if leading_zero_bits(&Sha256::digest(solution.as_bytes())) < difficulty {
+            return Err("Invalid captcha");
+        }
+
+        let mut used = self.used.lock().unwrap();
+        used.retain(|_, issued| now - *issued <= CHALLENGE_TTL_SECS);
+        if used.insert(challenge.to_string(), issued).is_some() {
+            return Err("Captcha already used");
+        }
+        Ok(())
+    }
+
+    /// Verifies the solution sent in the `X-Captcha` header.
+    pub fn verify_headers(&self, headers: &HeaderMap) -> Result<(), &'static str> {
+        let solution = headers.get(CAPTCHA_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .ok_or("Captcha required")?;
+        self.verify(solution)
+    }
+}
@@ -0,0 +1,20 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Browsers the admin has logged in from, keyed by a hash of the User-Agent.
+// A login from an unknown one triggers a notification email.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "admin_device")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub device_hash: String,
+    pub user_agent: String,
+    pub last_ip: String,
+    pub first_seen: DateTime,
+    pub last_seen: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -12,6 +12,10 @@ pub struct Model {
     pub path_name: Option<String>,
     pub icon: Option<String>,
     pub color: Option<String>,
+    // None for a top-level category
+    pub parent_id: Option<i32>,
+    // Position among its siblings, ascending
+    pub sort_order: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
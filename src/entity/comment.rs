@@ -0,0 +1,40 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Reader comment on a note or a talk (target_type 'note' | 'talk').
+// status: 'pending' until moderated, then 'approved' or 'spam'.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "comment")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub target_type: String,
+    pub target_id: i32,
+    pub parent_id: Option<i32>,
+    pub author_name: String,
+    pub author_email: String,
+    pub author_website: Option<String>,
+    #[sea_orm(column_type = "Text")]
+    pub content: String,
+    #[sea_orm(column_type = "Text")]
+    pub content_html: String,
+    pub status: String,
+    pub is_admin: bool,
+    pub trained_as: Option<String>, // 'spam' | 'ham' once the classifier learned from it
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "Entity",
+        from = "Column::ParentId",
+        to = "Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Parent,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,31 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Background job. `unique_key` is only set while the job is queued or running,
+// so the unique index stops duplicates of pending work but not reruns later.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "job")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub job_type: String,
+    #[sea_orm(column_type = "Text")]
+    pub payload: String, // JSON
+    pub status: String, // queued / running / done / dead / cancelled
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub unique_key: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub last_error: Option<String>,
+    pub run_at: DateTime,
+    pub locked_by: Option<String>,
+    pub locked_at: Option<DateTime>,
+    pub finished_at: Option<DateTime>,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
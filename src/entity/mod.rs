@@ -7,3 +7,16 @@ pub mod friend;
 pub mod talk;
 pub mod web_info;
 pub mod image;
+pub mod note_revision;
+pub mod note_slug_redirect;
+pub mod note_tag;
+pub mod series;
+pub mod series_note;
+pub mod note_view;
+pub mod reaction;
+pub mod comment;
+pub mod spam_token;
+pub mod admin_device;
+pub mod webhook;
+pub mod webhook_delivery;
+pub mod job;
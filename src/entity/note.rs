@@ -22,6 +22,10 @@ pub struct Model {
     #[sea_orm(column_type = "Text", nullable)]
     pub tags: Option<String>,
     pub category_id: Option<i32>,
+    pub publish_at: Option<DateTime>, // go-live time for 'scheduled' notes (UTC)
+    #[sea_orm(unique)]
+    pub slug: Option<String>,
+    pub view_count: i32, // unique visitors per day, summed; kept up to date by views::flush
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
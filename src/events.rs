@@ -0,0 +1,103 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use crate::entity::note;
+use crate::routes::AppState;
+use crate::{mailer, scheduler, webhooks};
+
+/// Something that happened to the site's content. Handlers publish these after
+/// their writes succeeded; side effects live in subscribers.
+#[derive(Clone, Debug)]
+pub enum DomainEvent {
+    NoteCreated(note::Model),
+    NoteUpdated { note: note::Model, was_visible: bool },
+    NoteDeleted(note::Model),
+    // A scheduled note went live
+    NotePublished(note::Model),
+    TalkCreated { id: i32 },
+    TalkUpdated { id: i32 },
+    TalkDeleted { id: i32 },
+    ImageUploaded { id: i32, url: String },
+    ImagesDeleted { urls: Vec<String> },
+    FriendSubmitted { id: i32, name: String, url: String, description: String },
+}
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type Handler = Box<dyn Fn(Arc<AppState>, Arc<DomainEvent>) -> BoxFuture + Send + Sync>;
+
+/// In-process event bus. Publishing never blocks; a single background task hands
+/// events to every subscriber in publish order.
+pub struct EventBus {
+    tx: UnboundedSender<Arc<DomainEvent>>,
+    rx: Mutex<Option<UnboundedReceiver<Arc<DomainEvent>>>>,
+    subscribers: RwLock<Vec<(&'static str, Handler)>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (tx, rx) = unbounded_channel();
+        Self { tx, rx: Mutex::new(Some(rx)), subscribers: RwLock::new(Vec::new()) }
+    }
+}
+
+impl EventBus {
+    pub fn subscribe<F, Fut>(&self, name: &'static str, handler: F)
+    where
+        F: Fn(Arc<AppState>, Arc<DomainEvent>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler: Handler = Box::new(move |state, event| Box::pin(handler(state, event)));
+        self.subscribers.write().unwrap().push((name, handler));
+    }
+
+    pub fn publish(&self, event: DomainEvent) {
+        // Only fails once the dispatcher is gone, i.e. during shutdown
+        let _ = self.tx.send(Arc::new(event));
+    }
+
+    /// Runs every subscriber for one event. A panicking subscriber is logged and
+    /// does not affect the others.
+    pub async fn deliver(&self, state: &Arc<AppState>, event: Arc<DomainEvent>) {
+        let pending: Vec<(&'static str, BoxFuture)> = self.subscribers.read().unwrap()
+            .iter()
+            .map(|(name, handler)| (*name, handler(state.clone(), event.clone())))
+            .collect();
+        for (name, fut) in pending {
+            if let Err(e) = tokio::spawn(fut).await {
+                tracing::error!("Event subscriber {} failed: {}", name, e);
+            }
+        }
+    }
+}
+
+/// The side effects the blog ships with.
+pub fn register_default_subscribers(bus: &EventBus) {
+    bus.subscribe("webhooks", webhooks::on_event);
+    bus.subscribe("mailer", mailer::on_event);
+    bus.subscribe("scheduler", scheduler::on_event);
+    bus.subscribe("render_cache", |state: Arc<AppState>, event: Arc<DomainEvent>| async move {
+        match event.as_ref() {
+            DomainEvent::NoteUpdated { note, .. } | DomainEvent::NoteDeleted(note) => state.render_cache.invalidate(note.id),
+            _ => {}
+        }
+    });
+    // Protected writes already clear it on the way out; this covers scheduled publishing
+    bus.subscribe("response_cache", |state: Arc<AppState>, event: Arc<DomainEvent>| async move {
+        if let DomainEvent::NotePublished(_) = event.as_ref() {
+            state.response_cache.clear();
+        }
+    });
+}
+
+/// Starts the task that feeds published events to the subscribers.
+pub fn spawn(state: Arc<AppState>) {
+    let Some(mut rx) = state.events.rx.lock().unwrap().take() else {
+        return;
+    };
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            state.events.deliver(&state, event).await;
+        }
+    });
+}
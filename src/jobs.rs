@@ -0,0 +1,264 @@
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Set, Statement,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+use crate::entity::job;
+use crate::{mailer, scheduler, webhooks};
+use crate::routes::AppState;
+
+pub const STATUS_QUEUED: &str = "queued";
+pub const STATUS_RUNNING: &str = "running";
+pub const STATUS_DONE: &str = "done";
+// Out of attempts (or no handler); stays until an admin retries it
+pub const STATUS_DEAD: &str = "dead";
+pub const STATUS_CANCELLED: &str = "cancelled";
+
+pub const DEFAULT_MAX_ATTEMPTS: i32 = 5;
+const RETRY_BASE_SECS: i64 = 30;
+const MAX_RETRY_SECS: i64 = 6 * 3600;
+const IDLE_POLL: Duration = Duration::from_secs(5);
+const MAX_ERROR_CHARS: usize = 1000;
+
+type BoxFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+type Handler = Box<dyn Fn(Arc<AppState>, Value) -> BoxFuture + Send + Sync>;
+
+/// Job handlers by type, plus a signal that wakes an idle worker.
+#[derive(Default)]
+pub struct JobQueue {
+    handlers: RwLock<HashMap<&'static str, Handler>>,
+    wake: Notify,
+}
+
+impl JobQueue {
+    /// Registers the code run for `job_type`. An `Err` schedules a retry.
+    pub fn register<F, Fut>(&self, job_type: &'static str, handler: F)
+    where
+        F: Fn(Arc<AppState>, Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let handler: Handler = Box::new(move |state, payload| Box::pin(handler(state, payload)));
+        self.handlers.write().unwrap().insert(job_type, handler);
+    }
+
+    fn start(&self, state: Arc<AppState>, job_type: &str, payload: Value) -> Option<BoxFuture> {
+        self.handlers.read().unwrap().get(job_type).map(|h| h(state, payload))
+    }
+}
+
+/// Job types the blog ships with.
+pub fn register_default_handlers(queue: &JobQueue) {
+    queue.register(mailer::JOB_SEND_EMAIL, mailer::run_job);
+    queue.register(webhooks::JOB_DELIVER, webhooks::run_job);
+    queue.register(scheduler::JOB_PUBLISH, scheduler::run_job);
+}
+
+/// Delay before the next attempt: 30s, 2m, 8m, ... capped at six hours.
+pub fn backoff_secs(attempts: i32) -> i64 {
+    let exponent = attempts.clamp(1, 10) as u32 - 1;
+    (RETRY_BASE_SECS * 4i64.pow(exponent)).min(MAX_RETRY_SECS)
+}
+
+/// Inserts a job. With a `unique_key`, an existing queued or running job with the
+/// same key is returned instead of adding another one.
+pub async fn enqueue<C: ConnectionTrait>(db: &C, job_type: &str, payload: Value, unique_key: Option<&str>) -> Result<i32, DbErr> {
+    if let Some(key) = unique_key {
+        if let Some(existing) = job::Entity::find().filter(job::Column::UniqueKey.eq(key)).one(db).await? {
+            return Ok(existing.id);
+        }
+    }
+
+    insert(db, job_type, payload, unique_key, chrono::Utc::now().naive_utc()).await
+}
+
+/// Inserts a job due at `run_at`. If one with the same key is still queued it is
+/// moved to `run_at` instead, so rescheduling does not add a second job. One that is
+/// already running for another time gives up the key to a new job due at `run_at`.
+pub async fn enqueue_at<C: ConnectionTrait>(
+    db: &C,
+    job_type: &str,
+    payload: Value,
+    unique_key: &str,
+    run_at: chrono::NaiveDateTime,
+) -> Result<i32, DbErr> {
+    if let Some(existing) = job::Entity::find().filter(job::Column::UniqueKey.eq(unique_key)).one(db).await? {
+        if existing.run_at == run_at || existing.status != STATUS_RUNNING {
+            let id = existing.id;
+            if existing.status == STATUS_QUEUED && existing.run_at != run_at {
+                let mut active: job::ActiveModel = existing.into();
+                active.run_at = Set(run_at);
+                active.updated_at = Set(chrono::Utc::now().naive_utc());
+                active.update(db).await?;
+            }
+            return Ok(id);
+        }
+        job::Entity::update_many()
+            .col_expr(job::Column::UniqueKey, Expr::value(Option::<String>::None))
+            .filter(job::Column::Id.eq(existing.id))
+            .exec(db)
+            .await?;
+    }
+    insert(db, job_type, payload, Some(unique_key), run_at).await
+}
+
+async fn insert<C: ConnectionTrait>(
+    db: &C,
+    job_type: &str,
+    payload: Value,
+    unique_key: Option<&str>,
+    run_at: chrono::NaiveDateTime,
+) -> Result<i32, DbErr> {
+    let now = chrono::Utc::now().naive_utc();
+    let new_job = job::ActiveModel {
+        job_type: Set(job_type.to_string()),
+        payload: Set(payload.to_string()),
+        status: Set(STATUS_QUEUED.to_string()),
+        attempts: Set(0),
+        max_attempts: Set(DEFAULT_MAX_ATTEMPTS),
+        unique_key: Set(unique_key.map(str::to_string)),
+        run_at: Set(run_at),
+        created_at: Set(now),
+        updated_at: Set(now),
+        ..Default::default()
+    };
+    Ok(new_job.insert(db).await?.id)
+}
+
+/// `enqueue` plus waking a worker, for use from handlers.
+pub async fn push(state: &AppState, job_type: &str, payload: Value, unique_key: Option<&str>) -> Result<i32, DbErr> {
+    let id = enqueue(&state.db, job_type, payload, unique_key).await?;
+    wake(state);
+    Ok(id)
+}
+
+/// Wakes an idle worker, e.g. after jobs were requeued by hand.
+pub fn wake(state: &AppState) {
+    state.jobs.wake.notify_one();
+}
+
+/// Marks the oldest due job as running for this worker and returns it.
+async fn claim<C: ConnectionTrait>(db: &C) -> Result<Option<job::Model>, DbErr> {
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    let now = chrono::Utc::now().naive_utc();
+    // A single UPDATE ... LIMIT 1 so two workers never take the same row
+    let claimed = db.execute(Statement::from_sql_and_values(
+        db.get_database_backend(),
+        "UPDATE `job` SET `status` = ?, `locked_by` = ?, `locked_at` = ?, `attempts` = `attempts` + 1, `updated_at` = ? \
+         WHERE `status` = ? AND `run_at` <= ? ORDER BY `run_at`, `id` LIMIT 1",
+        [STATUS_RUNNING.into(), token.clone().into(), now.into(), now.into(), STATUS_QUEUED.into(), now.into()],
+    )).await?;
+    if claimed.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    job::Entity::find()
+        .filter(job::Column::LockedBy.eq(token))
+        .filter(job::Column::Status.eq(STATUS_RUNNING))
+        .one(db)
+        .await
+}
+
+async fn finish<C: ConnectionTrait>(db: &C, j: job::Model, result: Result<(), String>) -> Result<(), DbErr> {
+    let now = chrono::Utc::now().naive_utc();
+    let attempts = j.attempts;
+    let max_attempts = j.max_attempts;
+    let mut active: job::ActiveModel = j.into();
+    active.locked_by = Set(None);
+    active.locked_at = Set(None);
+    active.updated_at = Set(now);
+
+    match result {
+        Ok(()) => {
+            active.status = Set(STATUS_DONE.to_string());
+            active.unique_key = Set(None);
+            active.last_error = Set(None);
+            active.finished_at = Set(Some(now));
+        }
+        Err(e) => {
+            active.last_error = Set(Some(e.chars().take(MAX_ERROR_CHARS).collect()));
+            if attempts >= max_attempts {
+                active.status = Set(STATUS_DEAD.to_string());
+                active.unique_key = Set(None);
+                active.finished_at = Set(Some(now));
+            } else {
+                active.status = Set(STATUS_QUEUED.to_string());
+                active.run_at = Set(now + chrono::Duration::seconds(backoff_secs(attempts)));
+            }
+        }
+    }
+    active.update(db).await.map(|_| ())
+}
+
+/// Claims and runs one due job. Returns false when there was nothing to do.
+pub async fn run_next(state: &Arc<AppState>) -> Result<bool, DbErr> {
+    let Some(j) = claim(&state.db).await? else {
+        return Ok(false);
+    };
+
+    let payload = serde_json::from_str(&j.payload).map_err(|e| format!("Invalid payload: {}", e));
+    let fut = payload.and_then(|p| {
+        state.jobs.start(state.clone(), &j.job_type, p).ok_or_else(|| format!("No handler for job type {}", j.job_type))
+    });
+    // A bad payload or an unknown type cannot succeed later, so those go straight to dead
+    let retryable = fut.is_ok();
+    let result = match fut {
+        // Run in its own task so a panicking handler only fails its job
+        Ok(fut) => tokio::spawn(fut).await.unwrap_or_else(|e| Err(format!("Job panicked: {}", e))),
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = &result {
+        tracing::warn!("Job {} ({}) attempt {} failed: {}", j.id, j.job_type, j.attempts, e);
+    }
+    let mut j = j;
+    if !retryable {
+        j.attempts = j.max_attempts;
+    }
+    finish(&state.db, j, result).await?;
+    Ok(true)
+}
+
+/// Jobs left running by a previous process never finished; queue them again.
+pub async fn requeue_interrupted<C: ConnectionTrait>(db: &C) -> Result<u64, DbErr> {
+    job::Entity::update_many()
+        .col_expr(job::Column::Status, Expr::value(STATUS_QUEUED))
+        .col_expr(job::Column::LockedBy, Expr::value(Option::<String>::None))
+        .col_expr(job::Column::LockedAt, Expr::value(Option::<chrono::NaiveDateTime>::None))
+        .filter(job::Column::Status.eq(STATUS_RUNNING))
+        .exec(db)
+        .await
+        .map(|r| r.rows_affected)
+}
+
+/// Starts `workers` tasks that keep taking jobs off the queue.
+pub fn spawn(state: Arc<AppState>, workers: usize) {
+    tokio::spawn(async move {
+        match requeue_interrupted(&state.db).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Requeued {} interrupted job(s)", n),
+            Err(e) => tracing::error!("Requeueing interrupted jobs failed: {}", e),
+        }
+
+        for _ in 0..workers {
+            let state = state.clone();
+            tokio::spawn(async move {
+                loop {
+                    match run_next(&state).await {
+                        Ok(true) => continue,
+                        Ok(false) => {}
+                        Err(e) => tracing::error!("Job worker error: {}", e),
+                    }
+                    tokio::select! {
+                        _ = state.jobs.wake.notified() => {}
+                        _ = tokio::time::sleep(IDLE_POLL) => {}
+                    }
+                }
+            });
+        }
+    });
+}
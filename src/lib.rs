@@ -2,5 +2,18 @@ pub mod entity;
 pub mod routes;
 pub mod utils;
 pub mod middleware;
+pub mod markdown;
+pub mod scheduler;
+pub mod slug;
+pub mod views;
+pub mod rate_limit;
+pub mod spam;
+pub mod captcha;
+pub mod mailer;
+pub mod webhooks;
+pub mod events;
+pub mod jobs;
+pub mod response_cache;
+pub mod text;
 
 pub use routes::{create_router, AppState};
@@ -0,0 +1,291 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use sea_orm::{ConnectionTrait, DbErr, EntityTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{self, OwnedTrustAnchor, RootCertStore, ServerName};
+use tokio_rustls::TlsConnector;
+use crate::entity::web_info;
+use crate::events::DomainEvent;
+use crate::routes::{web_info::base_url, AppState};
+use crate::jobs;
+use crate::utils::format_datetime;
+
+pub const JOB_SEND_EMAIL: &str = "email.send";
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Security {
+    None,
+    StartTls,
+    // Implicit TLS, usually port 465
+    Tls,
+}
+
+/// SMTP account and recipients, read from the `web_info` settings.
+#[derive(Clone, Debug)]
+pub struct MailSettings {
+    pub host: String,
+    pub port: u16,
+    pub security: Security,
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    // Where admin notifications go
+    pub admin_to: String,
+    pub site_title: String,
+    pub base_url: String,
+}
+
+impl MailSettings {
+    pub fn is_configured(&self) -> bool {
+        !self.host.is_empty() && !self.from.is_empty()
+    }
+}
+
+pub async fn load_settings<C: ConnectionTrait>(db: &C) -> Result<MailSettings, DbErr> {
+    let infos = web_info::Entity::find().all(db).await?;
+    let get_val = |k: &str| -> String {
+        infos.iter().find(|i| i.key_name == k).map(|i| i.value.trim().to_string()).unwrap_or_default()
+    };
+
+    let security = match get_val("smtpSecurity").to_lowercase().as_str() {
+        "none" => Security::None,
+        "starttls" => Security::StartTls,
+        _ => Security::Tls,
+    };
+    let default_port = match security {
+        Security::None => 25,
+        Security::StartTls => 587,
+        Security::Tls => 465,
+    };
+    let notify = get_val("notifyEmail");
+
+    Ok(MailSettings {
+        host: get_val("smtpHost"),
+        port: get_val("smtpPort").parse().unwrap_or(default_port),
+        security,
+        username: get_val("smtpUsername"),
+        password: get_val("smtpPassword"),
+        from: get_val("smtpFrom"),
+        admin_to: if notify.is_empty() { get_val("socialEmail") } else { notify },
+        site_title: get_val("blog_title"),
+        base_url: base_url(&get_val("blogDomain")),
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Events that send an email. Rendering happens when the mail goes out, so the
+/// current settings (recipient, site name) are used.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Notification {
+    FriendSubmitted { name: String, url: String, description: String },
+    NewComment { author: String, email: String, content: String, target_type: String, target_id: i32, status: String },
+    // To the author of the comment that was replied to
+    CommentReply { to: String, to_name: String, author: String, content: String, target_type: String, target_id: i32 },
+    NewDeviceLogin { ip: String, user_agent: String, at: chrono::NaiveDateTime },
+}
+
+fn target_link(s: &MailSettings, target_type: &str, target_id: i32) -> String {
+    match target_type {
+        "note" => format!("{}/article/{}", s.base_url, target_id),
+        _ => format!("{}/talk", s.base_url),
+    }
+}
+
+impl Notification {
+    /// None when there is nobody to send it to.
+    pub fn render(&self, s: &MailSettings) -> Option<Email> {
+        let site = if s.site_title.is_empty() { "博客" } else { s.site_title.as_str() };
+        let admin = |subject: String, body: String| {
+            (!s.admin_to.is_empty()).then(|| Email { to: s.admin_to.clone(), subject, body })
+        };
+
+        match self {
+            Notification::FriendSubmitted { name, url, description } => admin(
+                format!("[{}] 新的友链申请：{}", site, name),
+                format!("收到新的友链申请，等待审核。\n\n站点名称：{}\n站点地址：{}\n站点描述：{}\n\n请到后台友链管理中处理。\n", name, url, description),
+            ),
+            Notification::NewComment { author, email, content, target_type, target_id, status } => admin(
+                format!("[{}] {} 发表了新评论", site, author),
+                format!("{} <{}> 发表了评论（状态：{}）：\n\n{}\n\n原文：{}\n", author, email, status, content, target_link(s, target_type, *target_id)),
+            ),
+            Notification::CommentReply { to, to_name, author, content, target_type, target_id } => Some(Email {
+                to: to.clone(),
+                subject: format!("[{}] {} 回复了你的评论", site, author),
+                body: format!("{}，你好：\n\n{} 回复了你在「{}」的评论：\n\n{}\n\n查看：{}\n", to_name, author, site, content, target_link(s, target_type, *target_id)),
+            }),
+            Notification::NewDeviceLogin { ip, user_agent, at } => admin(
+                format!("[{}] 后台在新设备上登录", site),
+                format!("管理员账号在一个新设备上登录。\n\n时间：{}\nIP：{}\n浏览器：{}\n\n如果不是你本人操作，请立即修改密码。\n", format_datetime(*at), ip, user_agent),
+            ),
+        }
+    }
+}
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+fn smtp_error(msg: String) -> io::Error {
+    io::Error::other(msg)
+}
+
+async fn read_reply(conn: &mut BufReader<Box<dyn Stream>>) -> io::Result<(u16, String)> {
+    let mut reply = String::new();
+    loop {
+        let mut line = String::new();
+        if conn.read_line(&mut line).await? == 0 {
+            return Err(smtp_error("SMTP server closed the connection".to_string()));
+        }
+        reply.push_str(&line);
+        // Multi-line replies use "250-" on every line but the last
+        if line.len() < 4 || line.as_bytes()[3] != b'-' {
+            let code = line.get(..3).and_then(|c| c.parse().ok()).ok_or_else(|| smtp_error(format!("Bad SMTP reply: {}", line.trim())))?;
+            return Ok((code, reply));
+        }
+    }
+}
+
+async fn command(conn: &mut BufReader<Box<dyn Stream>>, line: &str, expect: &[u16]) -> io::Result<()> {
+    conn.get_mut().write_all(format!("{}\r\n", line).as_bytes()).await?;
+    conn.get_mut().flush().await?;
+    expect_reply(conn, expect).await
+}
+
+async fn expect_reply(conn: &mut BufReader<Box<dyn Stream>>, expect: &[u16]) -> io::Result<()> {
+    let (code, reply) = read_reply(conn).await?;
+    if expect.contains(&code) {
+        Ok(())
+    } else {
+        Err(smtp_error(format!("Unexpected SMTP reply: {}", reply.trim())))
+    }
+}
+
+async fn tls_wrap(host: &str, tcp: Box<dyn Stream>) -> io::Result<Box<dyn Stream>> {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+    }));
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(host).map_err(|e| smtp_error(e.to_string()))?;
+    let tls = TlsConnector::from(Arc::new(config)).connect(name, tcp).await?;
+    Ok(Box::new(tls))
+}
+
+/// "=?UTF-8?B?...?=" so non-ASCII subjects and names survive any server.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", BASE64.encode(value))
+    }
+}
+
+fn message(s: &MailSettings, email: &Email) -> String {
+    let body = BASE64.encode(&email.body);
+    let lines: Vec<&str> = body.as_bytes().chunks(76).map(|c| std::str::from_utf8(c).unwrap_or_default()).collect();
+    let from = if s.site_title.is_empty() { s.from.clone() } else { format!("{} <{}>", encode_header(&s.site_title), s.from) };
+    let domain = s.from.rsplit_once('@').map(|(_, d)| d).unwrap_or("localhost");
+
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+        from,
+        email.to,
+        encode_header(&email.subject),
+        chrono::Utc::now().to_rfc2822(),
+        uuid::Uuid::new_v4().simple(),
+        domain,
+        lines.join("\r\n"),
+    )
+}
+
+async fn session(s: &MailSettings, email: &Email) -> io::Result<()> {
+    let tcp: Box<dyn Stream> = Box::new(TcpStream::connect((s.host.as_str(), s.port)).await?);
+    let stream = if s.security == Security::Tls { tls_wrap(&s.host, tcp).await? } else { tcp };
+    let mut conn = BufReader::new(stream);
+
+    expect_reply(&mut conn, &[220]).await?;
+    command(&mut conn, "EHLO localhost", &[250]).await?;
+    if s.security == Security::StartTls {
+        command(&mut conn, "STARTTLS", &[220]).await?;
+        conn = BufReader::new(tls_wrap(&s.host, conn.into_inner()).await?);
+        command(&mut conn, "EHLO localhost", &[250]).await?;
+    }
+    if !s.username.is_empty() {
+        let credentials = BASE64.encode(format!("\0{}\0{}", s.username, s.password));
+        command(&mut conn, &format!("AUTH PLAIN {}", credentials), &[235]).await?;
+    }
+
+    command(&mut conn, &format!("MAIL FROM:<{}>", s.from), &[250]).await?;
+    command(&mut conn, &format!("RCPT TO:<{}>", email.to), &[250, 251]).await?;
+    command(&mut conn, "DATA", &[354]).await?;
+    // The base64 body never starts a line with ".", so no dot-stuffing is needed
+    command(&mut conn, &format!("{}.", message(s, email)), &[250]).await?;
+    let _ = command(&mut conn, "QUIT", &[221]).await;
+    Ok(())
+}
+
+/// Sends one email right away.
+pub async fn send(s: &MailSettings, email: &Email) -> io::Result<()> {
+    // Addresses end up in SMTP commands and headers, keep them on one line
+    let unsafe_address = |a: &str| a.is_empty() || a.chars().any(|c| c.is_control() || c == '<' || c == '>' || c.is_whitespace());
+    if unsafe_address(&email.to) || unsafe_address(&s.from) {
+        return Err(smtp_error(format!("Invalid address: {}", email.to)));
+    }
+    tokio::time::timeout(SMTP_TIMEOUT, session(s, email))
+        .await
+        .map_err(|_| smtp_error("SMTP timed out".to_string()))?
+}
+
+/// Queues a notification as a job, so handlers never wait on SMTP. Failures are
+/// logged; the triggering action has already succeeded.
+pub async fn notify(state: &AppState, notification: Notification) {
+    let payload = match serde_json::to_value(&notification) {
+        Ok(v) => v,
+        Err(e) => return tracing::error!("Encoding notification failed: {}", e),
+    };
+    if let Err(e) = jobs::push(state, JOB_SEND_EMAIL, payload, None).await {
+        tracing::error!("Queueing email failed: {}", e);
+    }
+}
+
+/// Job handler for `JOB_SEND_EMAIL`. Mail is skipped, not retried, while SMTP is
+/// not configured or there is nobody to send it to.
+pub async fn run_job(state: Arc<AppState>, payload: Value) -> Result<(), String> {
+    let notification: Notification = serde_json::from_value(payload).map_err(|e| format!("Invalid notification: {}", e))?;
+    let settings = load_settings(&state.db).await.map_err(|e| e.to_string())?;
+    if !settings.is_configured() {
+        tracing::debug!("SMTP is not configured, dropping notification");
+        return Ok(());
+    }
+    let Some(email) = notification.render(&settings) else {
+        return Ok(());
+    };
+    send(&settings, &email).await.map_err(|e| format!("Sending to {} failed: {}", email.to, e))
+}
+
+/// Event bus subscriber for content events that email the admin.
+pub async fn on_event(state: Arc<AppState>, event: Arc<DomainEvent>) {
+    if let DomainEvent::FriendSubmitted { name, url, description, .. } = event.as_ref() {
+        notify(&state, Notification::FriendSubmitted {
+            name: name.clone(),
+            url: url.clone(),
+            description: description.clone(),
+        }).await;
+    }
+}
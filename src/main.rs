@@ -2,9 +2,9 @@ use dotenvy::dotenv;
 use sea_orm::Database;
 use std::env;
 use std::net::SocketAddr;
-use tracing_subscriber;
+use std::sync::Arc;
 
-use saudade_blog::{create_router, AppState};
+use saudade_blog::{create_router, events, jobs, scheduler, slug, views, AppState};
 
 #[tokio::main]
 async fn main() {
@@ -14,7 +14,25 @@ async fn main() {
     let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let db = Database::connect(&db_url).await.expect("Failed to connect to DB");
 
-    let app_state = AppState { db };
+    match slug::backfill_missing(&db).await {
+        Ok(0) => {}
+        Ok(n) => println!("Generated slugs for {} existing notes", n),
+        Err(e) => eprintln!("Slug backfill failed: {}", e),
+    }
+
+    match scheduler::queue_scheduled(&db).await {
+        Ok(0) => {}
+        Ok(n) => println!("Checked publish jobs for {} scheduled notes", n),
+        Err(e) => eprintln!("Queueing scheduled notes failed: {}", e),
+    }
+
+    let app_state = Arc::new(AppState::new(db));
+    events::register_default_subscribers(&app_state.events);
+    events::spawn(app_state.clone());
+    views::spawn(app_state.clone());
+    jobs::register_default_handlers(&app_state.jobs);
+    jobs::spawn(app_state.clone(), 2);
+
     let app = create_router(app_state);
 
     let items = vec![
@@ -26,5 +44,5 @@ async fn main() {
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     println!("listening on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
@@ -0,0 +1,269 @@
+use chrono::NaiveDateTime;
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock, RwLock};
+use syntect::highlighting::ThemeSet;
+use syntect::html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+// Highlighted code uses CSS classes instead of inline styles so the sanitizer can stay strict.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+const HIGHLIGHT_THEME: &str = "InspiredGitHub";
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TocEntry {
+    pub level: u32,
+    pub text: String,
+    pub anchor: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderedNote {
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+}
+
+/// Rendered notes keyed by note id, valid as long as `updated_at` is unchanged.
+#[derive(Default)]
+pub struct RenderCache {
+    entries: RwLock<HashMap<i32, (NaiveDateTime, Arc<RenderedNote>)>>,
+}
+
+impl RenderCache {
+    pub fn get_or_render(&self, id: i32, updated_at: NaiveDateTime, markdown: &str) -> Arc<RenderedNote> {
+        if let Some((stamp, rendered)) = self.entries.read().unwrap().get(&id) {
+            if *stamp == updated_at {
+                return rendered.clone();
+            }
+        }
+
+        let rendered = Arc::new(render(markdown));
+        self.entries.write().unwrap().insert(id, (updated_at, rendered.clone()));
+        rendered
+    }
+
+    pub fn invalidate(&self, id: i32) {
+        self.entries.write().unwrap().remove(&id);
+    }
+}
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// Stylesheet matching the classes emitted for highlighted code blocks.
+pub fn highlight_css() -> &'static str {
+    static CSS: OnceLock<String> = OnceLock::new();
+    CSS.get_or_init(|| {
+        let themes = ThemeSet::load_defaults();
+        themes.themes.get(HIGHLIGHT_THEME)
+            .and_then(|theme| css_for_theme_with_class_style(theme, CLASS_STYLE).ok())
+            .unwrap_or_default()
+    })
+}
+
+fn heading_level(level: HeadingLevel) -> u32 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+/// Anchor for a heading: lowercase letters/digits (CJK kept as-is), everything else collapsed to `-`.
+pub fn anchor_for(text: &str) -> String {
+    let mut anchor = String::new();
+    for c in text.trim().chars() {
+        if c.is_alphanumeric() {
+            anchor.extend(c.to_lowercase());
+        } else if !anchor.ends_with('-') {
+            anchor.push('-');
+        }
+    }
+    let anchor = anchor.trim_matches('-').to_string();
+    if anchor.is_empty() { "section".to_string() } else { anchor }
+}
+
+fn highlight(code: &str, lang: &str) -> String {
+    let ss = syntax_set();
+    let syntax = ss.find_syntax_by_token(lang).unwrap_or_else(|| ss.find_syntax_plain_text());
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, ss, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        if generator.parse_html_for_line_which_includes_newline(line).is_err() {
+            return format!("<pre><code>{}</code></pre>", escape_html(code));
+        }
+    }
+    format!(
+        "<pre class=\"highlight\"><code class=\"language-{}\">{}</code></pre>",
+        escape_html(lang),
+        generator.finalize()
+    )
+}
+
+pub fn escape_html(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::default();
+        builder
+            .add_tag_attributes("h1", &["id"])
+            .add_tag_attributes("h2", &["id"])
+            .add_tag_attributes("h3", &["id"])
+            .add_tag_attributes("h4", &["id"])
+            .add_tag_attributes("h5", &["id"])
+            .add_tag_attributes("h6", &["id"])
+            .add_tag_attributes("div", &["id", "class"])
+            .add_tag_attributes("sup", &["class"])
+            .add_tag_attributes("pre", &["class"])
+            .add_tag_attributes("code", &["class"])
+            .add_tag_attributes("span", &["class"]);
+        builder
+    })
+}
+
+/// Renders note Markdown to sanitized HTML and extracts its table of contents.
+pub fn render(markdown: &str) -> RenderedNote {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH;
+
+    let mut events = Vec::new();
+    let mut toc = Vec::new();
+    let mut used_anchors = HashSet::new();
+
+    // Heading and code block contents are buffered so they can be rewritten as a whole.
+    let mut heading: Option<(HeadingLevel, Vec<Event>)> = None;
+    let mut code: Option<(String, String)> = None;
+
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => heading = Some((level, Vec::new())),
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, inner)) = heading.take() {
+                    let text: String = inner.iter().filter_map(|e| match e {
+                        Event::Text(t) | Event::Code(t) => Some(t.as_ref()),
+                        _ => None,
+                    }).collect();
+
+                    let base = anchor_for(&text);
+                    let mut anchor = base.clone();
+                    let mut n = 1;
+                    while !used_anchors.insert(anchor.clone()) {
+                        anchor = format!("{}-{}", base, n);
+                        n += 1;
+                    }
+
+                    let lvl = heading_level(level);
+                    let mut inner_html = String::new();
+                    html::push_html(&mut inner_html, inner.into_iter());
+
+                    events.push(Event::Html(CowStr::from(format!(
+                        "<h{lvl} id=\"{anchor}\">{inner_html}</h{lvl}>\n"
+                    ))));
+                    toc.push(TocEntry { level: lvl, text: text.trim().to_string(), anchor });
+                }
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or("").to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code = Some((lang, String::new()));
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((lang, body)) = code.take() {
+                    let block = if lang.is_empty() {
+                        format!("<pre><code>{}</code></pre>\n", escape_html(&body))
+                    } else {
+                        highlight(&body, &lang)
+                    };
+                    events.push(Event::Html(CowStr::from(block)));
+                }
+            }
+            Event::Text(t) if code.is_some() => {
+                if let Some((_, body)) = code.as_mut() { body.push_str(&t); }
+            }
+            e => match heading.as_mut() {
+                Some((_, inner)) => inner.push(e),
+                None => events.push(e),
+            },
+        }
+    }
+
+    let mut raw = String::new();
+    html::push_html(&mut raw, events.into_iter());
+
+    RenderedNote {
+        html: sanitizer().clean(&raw).to_string(),
+        toc,
+    }
+}
+
+/// Plain-text excerpt of a Markdown document, cut at `max_chars` characters.
+pub fn excerpt(markdown: &str, max_chars: usize) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let text = words.join(" ");
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+    let mut cut: String = text.chars().take(max_chars).collect();
+    cut.push('…');
+    cut
+}
+
+fn comment_sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::empty();
+        builder
+            .tags(HashSet::from([
+                "p", "br", "em", "strong", "del", "code", "pre", "blockquote", "ul", "ol", "li", "a",
+            ]))
+            .add_tag_attributes("a", &["href"])
+            .url_schemes(HashSet::from(["http", "https", "mailto"]))
+            .link_rel(Some("nofollow noopener noreferrer ugc"));
+        builder
+    })
+}
+
+/// Renders a reader comment. Much stricter than notes: raw HTML is shown as text,
+/// and only basic inline formatting, lists, quotes, code and links survive.
+pub fn render_comment(markdown: &str) -> String {
+    let events = Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        e => e,
+    });
+
+    let mut raw = String::new();
+    html::push_html(&mut raw, events);
+    comment_sanitizer().clean(&raw).to_string()
+}
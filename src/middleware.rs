@@ -1,21 +1,79 @@
 use axum::{
-    body::Body,
+    body::{to_bytes, Body},
     extract::Request,
-    http::{StatusCode, header},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
     middleware::Next,
     response::Response,
 };
+use crate::utils::{etag_for, is_not_modified};
 
-pub async fn auth_guard(req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
-    let auth_header = req.headers()
+/// Whether the request carries the admin token.
+pub fn is_admin(headers: &HeaderMap) -> bool {
+    let auth_header = headers
         .get(header::AUTHORIZATION)
         .and_then(|header| header.to_str().ok());
 
-    match auth_header {
-        Some(token) if token.starts_with("mock-token-for-") => {
-            // In a real app, verify signature/expiration here
-            Ok(next.run(req).await)
+    // In a real app, verify signature/expiration here
+    matches!(auth_header, Some(token) if token.starts_with("mock-token-for-"))
+}
+
+pub async fn auth_guard(req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
+    if is_admin(req.headers()) {
+        Ok(next.run(req).await)
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Gives successful JSON GET responses an `ETag` (weak, since compression may
+/// change the bytes) and answers `If-None-Match` / `If-Modified-Since` with 304.
+/// `Last-Modified` comes from the handler when it knows one; responses that
+/// already carry an `ETag`, like the feeds, handle validation themselves.
+pub async fn conditional_get(req: Request<Body>, next: Next) -> Response {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return next.run(req).await;
+    }
+    let request_headers = req.headers().clone();
+    let response = next.run(req).await;
+
+    let is_json = response.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    if response.status() != StatusCode::OK || !is_json || response.headers().contains_key(header::ETAG) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(b) => b,
+        Err(e) => {
+            tracing::warn!("Reading response body failed: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+
+    let etag = etag_for(&body);
+    let last_modified = parts.headers
+        .get(header::LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok())
+        .map(|dt| dt.naive_utc());
+    if let Ok(value) = HeaderValue::from_str(&format!("W/{}", etag)) {
+        parts.headers.insert(header::ETAG, value);
+    }
+    // Let browsers keep the body but check back before using it
+    parts.headers.entry(header::CACHE_CONTROL).or_insert(HeaderValue::from_static("no-cache"));
+
+    if is_not_modified(&request_headers, &etag, last_modified) {
+        let mut not_modified = Response::new(Body::empty());
+        *not_modified.status_mut() = StatusCode::NOT_MODIFIED;
+        for name in [header::ETAG, header::LAST_MODIFIED, header::CACHE_CONTROL] {
+            if let Some(value) = parts.headers.get(&name) {
+                not_modified.headers_mut().insert(name, value.clone());
+            }
         }
-        _ => Err(StatusCode::UNAUTHORIZED),
+        return not_modified;
     }
+    Response::from_parts(parts, Body::from(body))
 }
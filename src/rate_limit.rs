@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Fixed-window limiter keyed by an arbitrary string such as a client IP.
+pub struct RateLimiter {
+    max: u32,
+    window: Duration,
+    hits: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(max: u32, window: Duration) -> Self {
+        Self { max, window, hits: Mutex::new(HashMap::new()) }
+    }
+
+    /// Counts an attempt for `key`; false once the key is over its limit for the current window.
+    pub fn check(&self, key: &str) -> bool {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap();
+
+        // Keep the map from growing without bound
+        if hits.len() > 10_000 {
+            hits.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+        }
+
+        let entry = hits.entry(key.to_string()).or_insert((now, 0));
+        if now.duration_since(entry.0) >= self.window {
+            *entry = (now, 0);
+        }
+        entry.1 += 1;
+        entry.1 <= self.max
+    }
+
+    /// Whether `key` has already used up the current window, without counting an attempt.
+    pub fn is_limited(&self, key: &str) -> bool {
+        let hits = self.hits.lock().unwrap();
+        matches!(hits.get(key), Some((start, count)) if start.elapsed() < self.window && *count >= self.max)
+    }
+
+    pub fn reset(&self, key: &str) {
+        self.hits.lock().unwrap().remove(key);
+    }
+}
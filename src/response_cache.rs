@@ -0,0 +1,139 @@
+use axum::{
+    body::{to_bytes, Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use crate::middleware::is_admin;
+use crate::routes::AppState;
+
+const DEFAULT_TTL: Duration = Duration::from_secs(30);
+const MAX_ENTRIES: usize = 1024;
+// Larger responses are passed through uncached
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+struct Entry {
+    stored: Instant,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// Finished responses of public GET routes, keyed by path and query. Entries
+/// expire after a short TTL and everything is dropped on any admin write.
+pub struct ResponseCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<String, Entry>>,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL)
+    }
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, entries: RwLock::new(HashMap::new()) }
+    }
+
+    fn get(&self, key: &str) -> Option<Response> {
+        let entries = self.entries.read().unwrap();
+        let entry = entries.get(key).filter(|e| e.stored.elapsed() < self.ttl)?;
+        let mut response = Response::new(Body::from(entry.body.clone()));
+        *response.status_mut() = entry.status;
+        *response.headers_mut() = entry.headers.clone();
+        Some(response)
+    }
+
+    fn insert(&self, key: String, status: StatusCode, headers: HeaderMap, body: Bytes) {
+        let mut entries = self.entries.write().unwrap();
+        // Query strings make the key space open-ended, keep it bounded
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, e| e.stored.elapsed() < self.ttl);
+            if entries.len() >= MAX_ENTRIES {
+                entries.clear();
+            }
+        }
+        entries.insert(key, Entry { stored: Instant::now(), status, headers, body });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries.write().unwrap().clear();
+    }
+
+    /// Drops the cached responses of these paths, whatever their query string.
+    pub fn invalidate_paths(&self, paths: &[&str]) {
+        self.entries.write().unwrap().retain(|key, _| {
+            let path = key.split_once('?').map_or(key.as_str(), |(path, _)| path);
+            !paths.contains(&path)
+        });
+    }
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    code: u16,
+}
+
+// Handlers report failures as HTTP 200 with an error code in the body; those must not stick
+fn is_cacheable(body: &[u8]) -> bool {
+    match serde_json::from_slice::<Envelope>(body) {
+        Ok(envelope) => envelope.code == 200,
+        Err(_) => true,
+    }
+}
+
+/// Route layer serving public GETs from the cache. The admin always gets a
+/// fresh response.
+pub async fn serve_cached(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    if req.method() != Method::GET || is_admin(req.headers()) {
+        return next.run(req).await;
+    }
+    let key = req.uri().path_and_query().map(|pq| pq.as_str().to_string()).unwrap_or_default();
+    if let Some(hit) = state.response_cache.get(&key) {
+        return hit;
+    }
+
+    let response = next.run(req).await;
+    let too_large = response.body().size_hint().upper().is_none_or(|n| n > MAX_BODY_BYTES as u64);
+    if response.status() != StatusCode::OK || too_large {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(e) => {
+            tracing::warn!("Reading response for {} failed: {}", key, e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    if is_cacheable(&body) {
+        state.response_cache.insert(key, parts.status, parts.headers.clone(), body.clone());
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Route layer for the protected routes: any write may change what the public
+/// pages show, so the whole cache is dropped once it is done.
+pub async fn invalidate_on_write(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    let write = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let response = next.run(req).await;
+    if write {
+        state.response_cache.clear();
+    }
+    response
+}
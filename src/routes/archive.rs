@@ -0,0 +1,118 @@
+use axum::{Json, extract::{State, Query}};
+use sea_orm::{EntityTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait, JoinType, FromQueryResult};
+use sea_orm::sea_query::Expr;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::entity::{category, note};
+use crate::routes::{AppState, notes};
+use crate::utils::{ApiResponse, format_datetime};
+
+// Notes are grouped by their Beijing-time date, the same clock the frontend displays
+pub(crate) const LOCAL_CREATED_AT: &str = "DATE_ADD(`note`.`created_at`, INTERVAL 8 HOUR)";
+
+#[derive(Deserialize)]
+pub struct ArchiveQuery {
+    pub year: Option<i32>,
+}
+
+#[derive(FromQueryResult)]
+struct ArchiveRow {
+    id: i32,
+    title: String,
+    slug: Option<String>,
+    created_at: chrono::NaiveDateTime,
+    category_id: Option<i32>,
+    category_title: Option<String>,
+    year: i32,
+    month: i32,
+}
+
+#[derive(Serialize)]
+pub struct ArchiveNoteDto {
+    #[serde(rename = "noteKey")]
+    pub id: i32,
+    #[serde(rename = "noteTitle")]
+    pub title: String,
+    pub slug: Option<String>,
+    #[serde(rename = "createTime")]
+    pub created_at: String,
+    #[serde(rename = "noteCategory")]
+    pub category_id: Option<i32>,
+    #[serde(rename = "categoryTitle")]
+    pub category_title: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ArchiveMonthDto {
+    pub month: i32,
+    pub count: usize,
+    pub notes: Vec<ArchiveNoteDto>,
+}
+
+#[derive(Serialize)]
+pub struct ArchiveYearDto {
+    pub year: i32,
+    pub count: usize,
+    pub months: Vec<ArchiveMonthDto>,
+}
+
+#[derive(Serialize, Default)]
+pub struct ArchiveDto {
+    pub total: usize,
+    pub years: Vec<ArchiveYearDto>,
+}
+
+// GET /api/public/archive?year=2024
+pub async fn get_archive(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ArchiveQuery>,
+) -> Json<ApiResponse<ArchiveDto>> {
+    let mut condition = notes::public_condition();
+    if let Some(year) = query.year {
+        condition = condition.add(Expr::cust(format!("YEAR({})", LOCAL_CREATED_AT)).eq(year));
+    }
+
+    // Only the listed columns are selected, content bodies never leave the database
+    let rows = note::Entity::find()
+        .select_only()
+        .columns([note::Column::Id, note::Column::Title, note::Column::Slug, note::Column::CreatedAt, note::Column::CategoryId])
+        .column_as(category::Column::Name, "category_title")
+        .column_as(Expr::cust(format!("YEAR({})", LOCAL_CREATED_AT)), "year")
+        .column_as(Expr::cust(format!("MONTH({})", LOCAL_CREATED_AT)), "month")
+        .join(JoinType::LeftJoin, note::Relation::Category.def())
+        .filter(condition)
+        .order_by_desc(note::Column::CreatedAt)
+        .into_model::<ArchiveRow>()
+        .all(&state.db)
+        .await;
+
+    let rows = match rows {
+        Ok(r) => r,
+        Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+    };
+
+    // Rows arrive newest first, so each year and month is one contiguous run
+    let mut archive = ArchiveDto { total: rows.len(), years: vec![] };
+    for r in rows {
+        if archive.years.last().map(|y| y.year) != Some(r.year) {
+            archive.years.push(ArchiveYearDto { year: r.year, count: 0, months: vec![] });
+        }
+        let year = archive.years.last_mut().unwrap();
+        year.count += 1;
+        if year.months.last().map(|m| m.month) != Some(r.month) {
+            year.months.push(ArchiveMonthDto { month: r.month, count: 0, notes: vec![] });
+        }
+        let month = year.months.last_mut().unwrap();
+        month.count += 1;
+        month.notes.push(ArchiveNoteDto {
+            id: r.id,
+            title: r.title,
+            slug: r.slug,
+            created_at: format_datetime(r.created_at),
+            category_id: r.category_id,
+            category_title: r.category_title,
+        });
+    }
+
+    Json(ApiResponse::success(archive))
+}
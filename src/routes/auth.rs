@@ -1,9 +1,14 @@
-use axum::{Json, extract::State};
-use sea_orm::{EntityTrait, ColumnTrait, QueryFilter};
+use axum::{Json, extract::{ConnectInfo, State}, http::{header, HeaderMap}};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, ActiveModelTrait, Set, DbErr};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use crate::entity::user;
+use crate::captcha::ChallengeDto;
+use crate::entity::{admin_device, user};
+use crate::mailer::{self, Notification};
 use crate::routes::AppState;
 use crate::utils::{ApiResponse, encrypt_password};
+use crate::views;
 use serde::Deserialize;
 
 #[derive(Deserialize)]
@@ -12,10 +17,60 @@ pub struct LoginRequest {
     password: String,
 }
 
+// GET /api/public/captcha
+pub async fn captcha_challenge(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<ChallengeDto>> {
+    Json(ApiResponse::success(state.captcha.challenge()))
+}
+
+/// Remembers the browser the admin logged in from and emails the admin when it
+/// has not been seen before.
+async fn track_device(state: &AppState, ip: &str, headers: &HeaderMap) -> Result<(), DbErr> {
+    let user_agent: String = headers.get(header::USER_AGENT)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("")
+        .chars()
+        .take(512)
+        .collect();
+    let hash = hex::encode(Sha256::digest(user_agent.as_bytes()));
+    let now = chrono::Utc::now().naive_utc();
+
+    match admin_device::Entity::find_by_id(hash.clone()).one(&state.db).await? {
+        Some(device) => {
+            let mut active: admin_device::ActiveModel = device.into();
+            active.last_ip = Set(ip.to_string());
+            active.last_seen = Set(now);
+            active.update(&state.db).await?;
+        }
+        None => {
+            admin_device::ActiveModel {
+                device_hash: Set(hash),
+                user_agent: Set(user_agent.clone()),
+                last_ip: Set(ip.to_string()),
+                first_seen: Set(now),
+                last_seen: Set(now),
+            }.insert(&state.db).await?;
+            mailer::notify(state, Notification::NewDeviceLogin { ip: ip.to_string(), user_agent, at: now }).await;
+        }
+    }
+    Ok(())
+}
+
 pub async fn login(
     State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(payload): Json<LoginRequest>,
 ) -> Json<ApiResponse<String>> {
+    // After a few wrong passwords from the same IP every attempt needs a solved captcha
+    let ip = views::client_ip(&headers, peer.map(|p| p.0));
+    if state.login_failures.is_limited(&ip) {
+        if let Err(msg) = state.captcha.verify_headers(&headers) {
+            return Json(ApiResponse { code: 428, message: msg.to_string(), data: String::new() });
+        }
+    }
+
     // Encrypt input username and password to match DB storage logic from Java
     let encrypted_info_username = encrypt_password(&payload.username);
     let encrypted_info_password = encrypt_password(&payload.password);
@@ -28,11 +83,16 @@ pub async fn login(
         .unwrap_or(None);
 
     if let Some(u) = user {
+        state.login_failures.reset(&ip);
+        if let Err(e) = track_device(&state, &ip, &headers).await {
+            tracing::warn!("Recording admin device failed: {}", e);
+        }
         // Generate a token.
         let token = format!("mock-token-for-{}", u.id);
         return Json(ApiResponse::success(token));
     }
 
+    state.login_failures.check(&ip);
     // Return generic error if not found
     Json(ApiResponse::error("账号或密码错误"))
 }
@@ -1,10 +1,12 @@
 use axum::{Json, extract::{State, Path}};
-use sea_orm::{EntityTrait, ActiveModelTrait, Set, PaginatorTrait, ColumnTrait, QueryFilter, IntoActiveModel};
+use sea_orm::{EntityTrait, ActiveModelTrait, Set, ColumnTrait, QueryFilter, QueryOrder, QuerySelect, ConnectionTrait, DbErr, FromQueryResult, TransactionTrait};
+use sea_orm::sea_query::{Expr, SimpleExpr};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use crate::entity::{category, note};
-use crate::routes::AppState;
-use crate::utils::ApiResponse;
+use crate::routes::{notes, AppState};
+use crate::utils::{ApiResponse, format_datetime};
 
 #[derive(Serialize)]
 pub struct CategoryDto {
@@ -23,6 +25,36 @@ pub struct CategoryDto {
     
     #[serde(rename = "noteCount")]
     pub note_count: i64,
+
+    // Admin listing only: how many of `noteCount` readers can see
+    #[serde(rename = "publicCount", skip_serializing_if = "Option::is_none")]
+    pub public_count: Option<i64>,
+
+    #[serde(rename = "latestNoteTime")]
+    pub latest_note_at: Option<String>,
+
+    #[serde(rename = "parentKey")]
+    pub parent_key: Option<i32>,
+    #[serde(rename = "sortOrder")]
+    pub sort_order: i32,
+}
+
+#[derive(Serialize)]
+pub struct CategoryNodeDto {
+    #[serde(flatten)]
+    pub category: CategoryDto,
+    pub children: Vec<CategoryNodeDto>,
+}
+
+/// One step of a note's category path, root first.
+#[derive(Serialize, Clone)]
+pub struct CategoryCrumbDto {
+    #[serde(rename = "categoryKey")]
+    pub category_key: i32,
+    #[serde(rename = "categoryTitle")]
+    pub category_title: String,
+    #[serde(rename = "pathName")]
+    pub path_name: String,
 }
 
 #[derive(Deserialize)]
@@ -36,31 +68,189 @@ pub struct CreateCategoryRequest {
     pub introduce: Option<String>,
     pub icon: Option<String>,
     pub color: Option<String>,
+
+    // 0 moves the category to the top level on update
+    #[serde(rename = "parentKey")]
+    pub parent_id: Option<i32>,
+    #[serde(rename = "sortOrder")]
+    pub sort_order: Option<i32>,
+}
+
+/// Every category in display order: by `sort_order`, then by id.
+pub(crate) async fn load_all<C: ConnectionTrait>(db: &C) -> Result<Vec<category::Model>, DbErr> {
+    category::Entity::find()
+        .order_by_asc(category::Column::SortOrder)
+        .order_by_asc(category::Column::Id)
+        .all(db)
+        .await
+}
+
+/// `id` and every category below it. Parent links are followed with a visited
+/// set, so rows that already form a loop cannot hang the walk.
+pub(crate) fn subtree_ids(cats: &[category::Model], id: i32) -> Vec<i32> {
+    let mut found = vec![id];
+    let mut seen = HashSet::from([id]);
+    let mut i = 0;
+    while i < found.len() {
+        let parent = found[i];
+        for c in cats.iter().filter(|c| c.parent_id == Some(parent)) {
+            if seen.insert(c.id) {
+                found.push(c.id);
+            }
+        }
+        i += 1;
+    }
+    found
+}
+
+/// Path from the root category down to `id`.
+pub(crate) fn breadcrumb(by_id: &HashMap<i32, &category::Model>, id: i32) -> Vec<CategoryCrumbDto> {
+    let mut path = Vec::new();
+    let mut seen = HashSet::new();
+    let mut current = Some(id);
+    while let Some(cid) = current {
+        let Some(c) = by_id.get(&cid).filter(|_| seen.insert(cid)) else {
+            break;
+        };
+        path.push(CategoryCrumbDto {
+            category_key: c.id,
+            category_title: c.name.clone(),
+            path_name: c.path_name.clone().unwrap_or_default(),
+        });
+        current = c.parent_id;
+    }
+    path.reverse();
+    path
+}
+
+/// Whether hanging `id` under `parent` would make it its own ancestor.
+pub fn creates_cycle(cats: &[category::Model], id: i32, parent: i32) -> bool {
+    subtree_ids(cats, id).contains(&parent)
+}
+
+/// Note filter for a category including all of its descendants.
+pub(crate) async fn subtree_filter<C: ConnectionTrait>(db: &C, id: i32) -> SimpleExpr {
+    let cats = load_all(db).await.unwrap_or_default();
+    note::Column::CategoryId.is_in(subtree_ids(&cats, id))
+}
+
+#[derive(FromQueryResult)]
+struct CategoryStats {
+    category_id: i32,
+    total: i64,
+    public: i64,
+    latest: Option<chrono::NaiveDateTime>,
+    latest_public: Option<chrono::NaiveDateTime>,
+}
+
+/// Note counts and newest note of every category in one grouped query. The
+/// public figures use the same visibility rules as the public note lists.
+async fn load_stats<C: ConnectionTrait>(db: &C) -> Result<HashMap<i32, CategoryStats>, DbErr> {
+    let public_only = |col: note::Column| -> SimpleExpr {
+        Expr::case(notes::public_condition(), Expr::col((note::Entity, col))).into()
+    };
+
+    let rows = note::Entity::find()
+        .select_only()
+        .column(note::Column::CategoryId)
+        .column_as(note::Column::Id.count(), "total")
+        .column_as(Expr::expr(public_only(note::Column::Id)).count(), "public")
+        .column_as(note::Column::CreatedAt.max(), "latest")
+        .column_as(Expr::expr(public_only(note::Column::CreatedAt)).max(), "latest_public")
+        .filter(note::Column::CategoryId.is_not_null())
+        .group_by(note::Column::CategoryId)
+        .into_model::<CategoryStats>()
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().map(|r| (r.category_id, r)).collect())
+}
+
+fn map_category(cat: category::Model, note_count: i64, public_count: Option<i64>, latest: Option<chrono::NaiveDateTime>) -> CategoryDto {
+    CategoryDto {
+        category_key: cat.id,
+        category_title: cat.name,
+        path_name: cat.path_name.unwrap_or_default(),
+        introduce: cat.introduce.unwrap_or_default(),
+        icon: cat.icon.unwrap_or_default(),
+        color: cat.color.unwrap_or_default(),
+        note_count,
+        public_count,
+        latest_note_at: latest.map(format_datetime),
+        parent_key: cat.parent_id,
+        sort_order: cat.sort_order,
+    }
+}
+
+fn public_dto(cat: category::Model, stats: &mut HashMap<i32, CategoryStats>) -> CategoryDto {
+    match stats.remove(&cat.id) {
+        Some(s) => map_category(cat, s.public, None, s.latest_public),
+        None => map_category(cat, 0, None, None),
+    }
 }
 
+/// Nests the flat list under `parent`, keeping the incoming order among siblings.
+fn build_tree(dtos: &mut Vec<CategoryDto>, parent: Option<i32>, known: &HashSet<i32>) -> Vec<CategoryNodeDto> {
+    // Anything with a missing parent is shown at the top level
+    let is_child = |d: &CategoryDto| match parent {
+        Some(p) => d.parent_key == Some(p),
+        None => d.parent_key.is_none_or(|p| !known.contains(&p)),
+    };
+    let (children, rest): (Vec<CategoryDto>, Vec<CategoryDto>) = std::mem::take(dtos).into_iter().partition(is_child);
+    *dtos = rest;
+    children.into_iter().map(|category| {
+        let children = build_tree(dtos, Some(category.category_key), known);
+        CategoryNodeDto { category, children }
+    }).collect()
+}
+
+// Counts only notes readers can see
 pub async fn list_categories(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<Vec<CategoryDto>>> {
-    let categories = category::Entity::find().all(&state.db).await.unwrap_or(vec![]);
-    
-    let mut dtos = Vec::new();
-    for cat in categories {
-        let count = note::Entity::find()
-            .filter(note::Column::CategoryId.eq(cat.id))
-            .count(&state.db)
-            .await
-            .unwrap_or(0);
-            
-        dtos.push(CategoryDto {
-            category_key: cat.id,
-            category_title: cat.name,
-            path_name: cat.path_name.unwrap_or_default(),
-            introduce: cat.introduce.unwrap_or_default(),
-            icon: cat.icon.unwrap_or_default(),
-            color: cat.color.unwrap_or_default(),
-            note_count: count as i64, 
-        });
-    }
+    let categories = load_all(&state.db).await.unwrap_or(vec![]);
+    let mut stats = load_stats(&state.db).await.unwrap_or_default();
+
+    let dtos = categories.into_iter().map(|cat| public_dto(cat, &mut stats)).collect();
+
+    Json(ApiResponse::success(dtos))
+}
+
+// GET /api/public/category/tree, counts are per category, not summed over children
+pub async fn list_category_tree(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<Vec<CategoryNodeDto>>> {
+    let categories = load_all(&state.db).await.unwrap_or(vec![]);
+    let mut stats = load_stats(&state.db).await.unwrap_or_default();
+
+    let known: HashSet<i32> = categories.iter().map(|c| c.id).collect();
+    let mut dtos: Vec<CategoryDto> = categories.into_iter().map(|cat| public_dto(cat, &mut stats)).collect();
+    let mut tree = build_tree(&mut dtos, None, &known);
+    // Leftovers sit in a parent loop that never reaches the top level
+    tree.extend(dtos.into_iter().map(|category| CategoryNodeDto { category, children: vec![] }));
+
+    Json(ApiResponse::success(tree))
+}
+
+// GET /api/protected/category, counts drafts and private notes too
+pub async fn list_admin_categories(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<Vec<CategoryDto>>> {
+    let loaded = async {
+        let categories = load_all(&state.db).await?;
+        let stats = load_stats(&state.db).await?;
+        Ok::<_, DbErr>((categories, stats))
+    }.await;
+    let (categories, mut stats) = match loaded {
+        Ok(v) => v,
+        Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+    };
+
+    let dtos = categories.into_iter().map(|cat| {
+        match stats.remove(&cat.id) {
+            Some(s) => map_category(cat, s.total, Some(s.public), s.latest),
+            None => map_category(cat, 0, Some(0), None),
+        }
+    }).collect();
 
     Json(ApiResponse::success(dtos))
 }
@@ -70,6 +260,14 @@ pub async fn create_category(
     Json(payload): Json<CreateCategoryRequest>,
 ) -> Json<ApiResponse<String>> {
     let name = payload.category_title.unwrap_or_else(|| "New Category".to_string());
+    let parent_id = payload.parent_id.filter(|p| *p != 0);
+    if let Some(p) = parent_id {
+        match category::Entity::find_by_id(p).one(&state.db).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return Json(ApiResponse::error("Parent category not found")),
+            Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+        }
+    }
     
     let new_cat = category::ActiveModel {
         name: Set(name),
@@ -77,6 +275,8 @@ pub async fn create_category(
         introduce: Set(payload.introduce),
         icon: Set(payload.icon),
         color: Set(payload.color),
+        parent_id: Set(parent_id),
+        sort_order: Set(payload.sort_order.unwrap_or(0)),
         ..Default::default()
     };
 
@@ -91,9 +291,26 @@ pub async fn update_category(
     Path(id): Path<i32>,
     Json(payload): Json<CreateCategoryRequest>,
 ) -> Json<ApiResponse<String>> {
-    let cat = category::Entity::find_by_id(id).one(&state.db).await.unwrap_or(None);
+    let cats = match load_all(&state.db).await {
+        Ok(c) => c,
+        Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+    };
+    let cat = cats.iter().find(|c| c.id == id).cloned();
     if let Some(c) = cat {
         let mut active: category::ActiveModel = c.into();
+
+        match payload.parent_id {
+            Some(0) => active.parent_id = Set(None),
+            Some(p) if !cats.iter().any(|c| c.id == p) => return Json(ApiResponse::error("Parent category not found")),
+            Some(p) if creates_cycle(&cats, id, p) => {
+                return Json(ApiResponse::error("A category cannot be moved under itself or its descendants"));
+            }
+            Some(p) => active.parent_id = Set(Some(p)),
+            None => {}
+        }
+        if let Some(v) = payload.sort_order {
+            active.sort_order = Set(v);
+        }
         
         if let Some(ref v) = payload.category_title {
             if !v.is_empty() {
@@ -134,15 +351,67 @@ pub async fn update_category(
     }
 }
 
+// Children of a deleted category move up to its nearest surviving ancestor
 pub async fn delete_category(
     State(state): State<Arc<AppState>>,
     Json(keys): Json<Vec<i32>>,
 ) -> Json<ApiResponse<String>> {
-    match category::Entity::delete_many()
-        .filter(category::Column::Id.is_in(keys))
-        .exec(&state.db)
-        .await {
+    let deleted = async {
+        let cats = load_all(&state.db).await?;
+        let by_id: HashMap<i32, &category::Model> = cats.iter().map(|c| (c.id, c)).collect();
+        let doomed: HashSet<i32> = keys.iter().copied().collect();
+
+        let txn = state.db.begin().await?;
+        for c in cats.iter().filter(|c| !doomed.contains(&c.id)) {
+            let Some(mut parent) = c.parent_id.filter(|p| doomed.contains(p)) else {
+                continue;
+            };
+            let mut seen = HashSet::new();
+            let new_parent = loop {
+                match by_id.get(&parent).and_then(|p| p.parent_id) {
+                    Some(up) if doomed.contains(&up) && seen.insert(up) => parent = up,
+                    Some(up) if !doomed.contains(&up) => break Some(up),
+                    _ => break None,
+                }
+            };
+            category::Entity::update_many()
+                .col_expr(category::Column::ParentId, Expr::value(new_parent))
+                .filter(category::Column::Id.eq(c.id))
+                .exec(&txn)
+                .await?;
+        }
+        category::Entity::delete_many()
+            .filter(category::Column::Id.is_in(keys))
+            .exec(&txn)
+            .await?;
+        txn.commit().await
+    }.await;
+
+    match deleted {
         Ok(_) => Json(ApiResponse::success("Deleted".to_string())),
         Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
     }
 }
+
+// POST /api/protected/category/reorder, Body: sibling ids in their new order
+pub async fn reorder_categories(
+    State(state): State<Arc<AppState>>,
+    Json(keys): Json<Vec<i32>>,
+) -> Json<ApiResponse<String>> {
+    let reordered = async {
+        let txn = state.db.begin().await?;
+        for (position, id) in keys.iter().enumerate() {
+            category::Entity::update_many()
+                .col_expr(category::Column::SortOrder, Expr::value(position as i32))
+                .filter(category::Column::Id.eq(*id))
+                .exec(&txn)
+                .await?;
+        }
+        txn.commit().await
+    }.await;
+
+    match reordered {
+        Ok(_) => Json(ApiResponse::success("Reordered".to_string())),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
@@ -0,0 +1,456 @@
+use axum::{
+    Json,
+    extract::{ConnectInfo, Query, State},
+    http::HeaderMap,
+};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, QueryOrder, ActiveModelTrait, Set, ConnectionTrait, DbErr, Condition, PaginatorTrait};
+use sea_orm::sea_query::Expr;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use crate::entity::comment;
+use crate::mailer::{self, Notification};
+use crate::markdown;
+use crate::middleware::is_admin;
+use crate::routes::{AppState, reactions};
+use crate::spam::{self, SpamInput, Verdict};
+use crate::utils::{ApiResponse, format_datetime};
+use crate::views;
+
+// What a comment hangs off; the same strings reactions use for their targets
+pub const TARGET_NOTE: &str = "note";
+pub const TARGET_TALK: &str = "talk";
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_APPROVED: &str = "approved";
+pub const STATUS_SPAM: &str = "spam";
+
+const MAX_AUTHOR_CHARS: usize = 32;
+const MAX_CONTENT_CHARS: usize = 2000;
+const MAX_URL_CHARS: usize = 255;
+const ADMIN_PAGE_SIZE: u64 = 20;
+
+#[derive(Deserialize)]
+pub struct CommentTarget {
+    #[serde(rename = "targetType")]
+    pub target_type: String,
+    #[serde(rename = "targetId")]
+    pub target_id: i32,
+}
+
+#[derive(Deserialize)]
+pub struct CreateCommentRequest {
+    #[serde(rename = "targetType")]
+    pub target_type: String,
+    #[serde(rename = "targetId")]
+    pub target_id: i32,
+    #[serde(rename = "parentKey")]
+    pub parent_id: Option<i32>,
+    pub author: String,
+    pub email: String,
+    pub website: Option<String>,
+    pub content: String,
+    // Hidden field, must stay empty
+    #[serde(rename = "hp")]
+    pub honeypot: Option<String>,
+    #[serde(rename = "formToken")]
+    pub form_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AdminCommentQuery {
+    pub status: Option<String>,
+    pub page: Option<u64>,
+}
+
+// Public view of an approved comment; the email is never exposed
+#[derive(Serialize)]
+pub struct CommentDto {
+    #[serde(rename = "commentKey")]
+    pub id: i32,
+    #[serde(rename = "parentKey")]
+    pub parent_id: Option<i32>,
+    pub author: String,
+    pub website: Option<String>,
+    #[serde(rename = "contentHtml")]
+    pub content_html: String,
+    #[serde(rename = "isAdmin")]
+    pub is_admin: bool,
+    #[serde(rename = "createTime")]
+    pub created_at: String,
+    pub replies: Vec<CommentDto>,
+}
+
+#[derive(Serialize)]
+pub struct AdminCommentDto {
+    #[serde(rename = "commentKey")]
+    pub id: i32,
+    #[serde(rename = "targetType")]
+    pub target_type: String,
+    #[serde(rename = "targetId")]
+    pub target_id: i32,
+    #[serde(rename = "parentKey")]
+    pub parent_id: Option<i32>,
+    pub author: String,
+    pub email: String,
+    pub website: Option<String>,
+    pub content: String,
+    #[serde(rename = "contentHtml")]
+    pub content_html: String,
+    pub status: String,
+    #[serde(rename = "isAdmin")]
+    pub is_admin: bool,
+    #[serde(rename = "createTime")]
+    pub created_at: String,
+}
+
+#[derive(Serialize, Default)]
+pub struct CreatedCommentDto {
+    #[serde(rename = "commentKey")]
+    pub id: i32,
+    pub status: String,
+}
+
+fn map_admin(c: comment::Model) -> AdminCommentDto {
+    AdminCommentDto {
+        id: c.id,
+        target_type: c.target_type,
+        target_id: c.target_id,
+        parent_id: c.parent_id,
+        author: c.author_name,
+        email: c.author_email,
+        website: c.author_website,
+        content: c.content,
+        content_html: c.content_html,
+        status: c.status,
+        is_admin: c.is_admin,
+        created_at: format_datetime(c.created_at),
+    }
+}
+
+/// Nests approved comments under their parents. A reply whose parent is not
+/// approved is dropped along with the rest of that branch.
+fn build_threads(comments: Vec<comment::Model>) -> Vec<CommentDto> {
+    let mut children: HashMap<Option<i32>, Vec<comment::Model>> = HashMap::new();
+    for c in comments {
+        children.entry(c.parent_id).or_default().push(c);
+    }
+
+    fn attach(parent: Option<i32>, children: &mut HashMap<Option<i32>, Vec<comment::Model>>) -> Vec<CommentDto> {
+        let Some(level) = children.remove(&parent) else {
+            return vec![];
+        };
+        level.into_iter().map(|c| CommentDto {
+            replies: attach(Some(c.id), children),
+            id: c.id,
+            parent_id: c.parent_id,
+            author: c.author_name,
+            website: c.author_website,
+            content_html: c.content_html,
+            is_admin: c.is_admin,
+            created_at: format_datetime(c.created_at),
+        }).collect()
+    }
+
+    attach(None, &mut children)
+}
+
+/// Removes the comments of deleted notes or talks.
+pub async fn delete_for<C: ConnectionTrait>(db: &C, target_type: &str, ids: &[i32]) -> Result<(), DbErr> {
+    comment::Entity::delete_many()
+        .filter(comment::Column::TargetType.eq(target_type))
+        .filter(comment::Column::TargetId.is_in(ids.to_vec()))
+        .exec(db)
+        .await
+        .map(|_| ())
+}
+
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty() && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.')
+        && !email.chars().any(|c| c.is_whitespace())
+}
+
+fn validate(payload: &CreateCommentRequest) -> Result<(), &'static str> {
+    let author = payload.author.trim();
+    if author.is_empty() || author.chars().count() > MAX_AUTHOR_CHARS {
+        return Err("Name must be 1-32 characters");
+    }
+    let email = payload.email.trim();
+    if email.len() > MAX_URL_CHARS || !is_valid_email(email) {
+        return Err("Invalid email");
+    }
+    if let Some(site) = payload.website.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        if site.len() > MAX_URL_CHARS || !(site.starts_with("http://") || site.starts_with("https://")) {
+            return Err("Website must be an http(s) URL");
+        }
+    }
+    let content = payload.content.trim();
+    if content.is_empty() || content.chars().count() > MAX_CONTENT_CHARS {
+        return Err("Comment must be 1-2000 characters");
+    }
+    Ok(())
+}
+
+/// Text the spam classifier learns from.
+fn training_text(c: &comment::Model) -> String {
+    format!("{} {} {} {}", c.author_name, c.author_email, c.author_website.as_deref().unwrap_or(""), c.content)
+}
+
+// GET /api/public/form-token, fetched when a comment or friend-link form is shown
+pub async fn form_token(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<String>> {
+    Json(ApiResponse::success(state.spam.issue_token()))
+}
+
+// GET /api/public/comments?targetType=note&targetId=1
+pub async fn list_public_comments(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<CommentTarget>,
+) -> Json<ApiResponse<Vec<CommentDto>>> {
+    let comments = comment::Entity::find()
+        .filter(comment::Column::TargetType.eq(target.target_type))
+        .filter(comment::Column::TargetId.eq(target.target_id))
+        .filter(comment::Column::Status.eq(STATUS_APPROVED))
+        .order_by_asc(comment::Column::CreatedAt)
+        .all(&state.db)
+        .await;
+
+    match comments {
+        Ok(c) => Json(ApiResponse::success(build_threads(c))),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+// POST /api/public/comments
+pub async fn create_comment(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateCommentRequest>,
+) -> Json<ApiResponse<CreatedCommentDto>> {
+    let admin = is_admin(&headers);
+    let ip = views::client_ip(&headers, peer.map(|p| p.0));
+    if !admin && !state.comment_limiter.check(&ip) {
+        return Json(ApiResponse {
+            code: 429,
+            message: "Too many comments, please try again later".to_string(),
+            data: CreatedCommentDto::default(),
+        });
+    }
+    if let Err(msg) = validate(&payload) {
+        return Json(ApiResponse::error(msg));
+    }
+    if !admin {
+        if let Err(msg) = state.captcha.verify_headers(&headers) {
+            return Json(ApiResponse { code: 428, message: msg.to_string(), data: CreatedCommentDto::default() });
+        }
+    }
+
+    match reactions::target_exists(&state.db, &payload.target_type, payload.target_id).await {
+        Ok(true) => {}
+        Ok(false) => return Json(ApiResponse::error("Not found")),
+        Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+
+    // Replies must stay in the same thread and only go under visible comments
+    if let Some(parent_id) = payload.parent_id {
+        let parent = comment::Entity::find_by_id(parent_id).one(&state.db).await.unwrap_or(None);
+        let valid = parent.is_some_and(|p| {
+            p.target_type == payload.target_type
+                && p.target_id == payload.target_id
+                && (p.status == STATUS_APPROVED || admin)
+        });
+        if !valid {
+            return Json(ApiResponse::error("Invalid parent comment"));
+        }
+    }
+
+    // Suspicious comments are kept for review under spam, blocked ones never stored
+    let status = if admin {
+        STATUS_APPROVED
+    } else {
+        let input = SpamInput {
+            author: payload.author.trim(),
+            email: payload.email.trim(),
+            website: payload.website.as_deref(),
+            content: payload.content.trim(),
+            honeypot: payload.honeypot.as_deref(),
+            form_token: payload.form_token.as_deref(),
+        };
+        match state.spam.evaluate(&state.db, &input).await {
+            Ok(Verdict::Ham) => STATUS_PENDING,
+            Ok(Verdict::Suspicious(reason)) => {
+                tracing::info!("Comment from {} held as spam: {}", ip, reason);
+                STATUS_SPAM
+            }
+            Ok(Verdict::Block(reason)) => {
+                tracing::warn!("Comment from {} blocked: {}", ip, reason);
+                return Json(ApiResponse::error("Comment rejected"));
+            }
+            Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+        }
+    };
+
+    let now = chrono::Utc::now().naive_utc();
+    let content = payload.content.trim().to_string();
+    let new_comment = comment::ActiveModel {
+        target_type: Set(payload.target_type),
+        target_id: Set(payload.target_id),
+        parent_id: Set(payload.parent_id),
+        author_name: Set(payload.author.trim().to_string()),
+        author_email: Set(payload.email.trim().to_string()),
+        author_website: Set(payload.website.map(|w| w.trim().to_string()).filter(|w| !w.is_empty())),
+        content_html: Set(markdown::render_comment(&content)),
+        content: Set(content),
+        status: Set(status.to_string()),
+        is_admin: Set(admin),
+        created_at: Set(now),
+        updated_at: Set(now),
+        ..Default::default()
+    };
+
+    let c = match new_comment.insert(&state.db).await {
+        Ok(c) => c,
+        Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+    };
+
+    if c.status == STATUS_PENDING {
+        mailer::notify(&state, Notification::NewComment {
+            author: c.author_name.clone(),
+            email: c.author_email.clone(),
+            content: c.content.clone(),
+            target_type: c.target_type.clone(),
+            target_id: c.target_id,
+            status: c.status.clone(),
+        }).await;
+    }
+    if c.status == STATUS_APPROVED {
+        if let Err(e) = notify_reply(&state, &c).await {
+            tracing::warn!("Reply notification for comment {} failed: {}", c.id, e);
+        }
+    }
+    Json(ApiResponse::success(CreatedCommentDto { id: c.id, status: c.status }))
+}
+
+// GET /api/protected/comments?status=pending&page=1
+pub async fn list_all_comments(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AdminCommentQuery>,
+) -> Json<ApiResponse<Vec<AdminCommentDto>>> {
+    let mut condition = Condition::all();
+    if let Some(status) = query.status.filter(|s| !s.is_empty()) {
+        condition = condition.add(comment::Column::Status.eq(status));
+    }
+
+    let select = comment::Entity::find()
+        .filter(condition)
+        .order_by_desc(comment::Column::CreatedAt);
+    let comments = match query.page {
+        Some(page) => select.paginate(&state.db, ADMIN_PAGE_SIZE).fetch_page(page.max(1) - 1).await,
+        None => select.all(&state.db).await,
+    };
+
+    match comments {
+        Ok(c) => Json(ApiResponse::success(c.into_iter().map(map_admin).collect())),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+/// Feeds a moderation decision to the spam classifier, undoing an earlier
+/// opposite decision on the same comment first.
+async fn train_decision<C: ConnectionTrait>(db: &C, c: comment::Model, label: &str) -> Result<(), DbErr> {
+    if c.is_admin || c.trained_as.as_deref() == Some(label) {
+        return Ok(());
+    }
+    let text = training_text(&c);
+    if let Some(previous) = c.trained_as.as_deref() {
+        spam::train(db, &text, previous, -1).await?;
+    }
+    spam::train(db, &text, label, 1).await?;
+
+    let mut active: comment::ActiveModel = c.into();
+    active.trained_as = Set(Some(label.to_string()));
+    active.update(db).await.map(|_| ())
+}
+
+/// Emails the author of the parent comment about a reply that just became visible.
+async fn notify_reply(state: &AppState, reply: &comment::Model) -> Result<(), DbErr> {
+    let Some(parent_id) = reply.parent_id else {
+        return Ok(());
+    };
+    let Some(parent) = comment::Entity::find_by_id(parent_id).one(&state.db).await? else {
+        return Ok(());
+    };
+    // The admin already hears about every comment, and nobody needs mail about answering themselves
+    if parent.is_admin || parent.author_email.eq_ignore_ascii_case(&reply.author_email) {
+        return Ok(());
+    }
+    mailer::notify(state, Notification::CommentReply {
+        to: parent.author_email,
+        to_name: parent.author_name,
+        author: reply.author_name.clone(),
+        content: reply.content.clone(),
+        target_type: reply.target_type.clone(),
+        target_id: reply.target_id,
+    }).await;
+    Ok(())
+}
+
+async fn set_status(state: &AppState, ids: Vec<i32>, status: &str, label: &str) -> Json<ApiResponse<String>> {
+    let updated = async {
+        let before = comment::Entity::find().filter(comment::Column::Id.is_in(ids.clone())).all(&state.db).await?;
+        let res = comment::Entity::update_many()
+            .col_expr(comment::Column::Status, Expr::value(status))
+            .col_expr(comment::Column::UpdatedAt, Expr::value(chrono::Utc::now().naive_utc()))
+            .filter(comment::Column::Id.is_in(ids))
+            .exec(&state.db)
+            .await?;
+        for c in before {
+            if status == STATUS_APPROVED && c.status != STATUS_APPROVED {
+                notify_reply(state, &c).await?;
+            }
+            train_decision(&state.db, c, label).await?;
+        }
+        Ok::<_, DbErr>(res.rows_affected)
+    }.await;
+
+    match updated {
+        Ok(n) => Json(ApiResponse::success(format!("Updated {} comments", n))),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+// POST /api/protected/comments/approve
+pub async fn approve_comments(
+    State(state): State<Arc<AppState>>,
+    Json(keys): Json<Vec<i32>>,
+) -> Json<ApiResponse<String>> {
+    set_status(&state, keys, STATUS_APPROVED, spam::LABEL_HAM).await
+}
+
+// POST /api/protected/comments/reject, marks the comments as spam and trains the filter
+pub async fn reject_comments(
+    State(state): State<Arc<AppState>>,
+    Json(keys): Json<Vec<i32>>,
+) -> Json<ApiResponse<String>> {
+    set_status(&state, keys, STATUS_SPAM, spam::LABEL_SPAM).await
+}
+
+// DELETE /api/protected/comments, replies go with their parents
+pub async fn delete_comments(
+    State(state): State<Arc<AppState>>,
+    Json(keys): Json<Vec<i32>>,
+) -> Json<ApiResponse<String>> {
+    match comment::Entity::delete_many()
+        .filter(comment::Column::Id.is_in(keys))
+        .exec(&state.db)
+        .await {
+        Ok(_) => Json(ApiResponse::success("Deleted".to_string())),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
@@ -0,0 +1,322 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sea_orm::{EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::entity::{category, note};
+use crate::markdown::{self, escape_html};
+use crate::routes::{AppState, categories, notes, tags};
+use crate::routes::tags::NoteTagDto;
+use crate::routes::web_info::{self, SiteMeta};
+use crate::utils::{etag_for, is_not_modified};
+
+const FEED_SIZE: u64 = 20;
+const EXCERPT_CHARS: usize = 200;
+
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    pub category: Option<i32>,
+    pub tag: Option<i32>,
+    pub tag_level: Option<i32>, // 1 or 2, only needed if the tag id is ambiguous
+}
+
+struct FeedItem {
+    id: i32,
+    title: String,
+    url: String,
+    summary: String,
+    content_html: String,
+    category: Option<String>,
+    tags: Vec<NoteTagDto>,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+}
+
+struct Feed {
+    site: SiteMeta,
+    title: String,
+    self_path: String,
+    items: Vec<FeedItem>,
+    updated: chrono::NaiveDateTime,
+    etag: String,
+}
+
+impl Feed {
+    fn self_url(&self) -> String {
+        format!("{}{}", self.site.base_url, self.self_path)
+    }
+
+    fn home_url(&self) -> String {
+        format!("{}/", self.site.base_url)
+    }
+}
+
+/// None while `blogDomain` is not set: feed readers need absolute links.
+async fn load_feed(state: &AppState, query: &FeedQuery, path: &str) -> Option<Feed> {
+    let site = web_info::load_site_meta(&state.db).await;
+    if site.base_url.is_empty() {
+        return None;
+    }
+
+    let mut condition = notes::public_condition();
+    let mut title = site.title.clone();
+    let mut params = Vec::new();
+
+    if let Some(cat_id) = query.category {
+        condition = condition.add(categories::subtree_filter(&state.db, cat_id).await);
+        params.push(format!("category={}", cat_id));
+        if let Ok(Some(c)) = category::Entity::find_by_id(cat_id).one(&state.db).await {
+            title = format!("{} - {}", site.title, c.name);
+        }
+    }
+    if let Some(tag_id) = query.tag {
+        condition = condition.add(tags::tag_filter(tag_id, query.tag_level));
+        params.push(format!("tag={}", tag_id));
+        if let Some(level) = query.tag_level {
+            params.push(format!("tag_level={}", level));
+        }
+        if let Ok(Some(name)) = tags::tag_name(&state.db, tag_id, query.tag_level).await {
+            title = format!("{} - #{}", title, name);
+        }
+    }
+
+    let rows = note::Entity::find()
+        .filter(condition)
+        .order_by_desc(note::Column::CreatedAt)
+        .limit(FEED_SIZE)
+        .find_also_related(category::Entity)
+        .all(&state.db)
+        .await
+        .unwrap_or(vec![]);
+
+    let ids: Vec<i32> = rows.iter().map(|(n, _)| n.id).collect();
+    let mut tag_map = tags::load_note_tags(&state.db, &ids).await.unwrap_or_default();
+
+    let items: Vec<FeedItem> = rows.into_iter().map(|(n, cat)| {
+        let rendered = state.render_cache.get_or_render(n.id, n.updated_at, &n.content);
+        let summary = n.description.clone()
+            .filter(|d| !d.trim().is_empty())
+            .unwrap_or_else(|| markdown::excerpt(&n.content, EXCERPT_CHARS));
+
+        FeedItem {
+            id: n.id,
+            url: format!("{}/article/{}", site.base_url, n.id),
+            title: n.title,
+            summary,
+            content_html: rendered.html.clone(),
+            category: cat.map(|c| c.name),
+            tags: tag_map.remove(&n.id).unwrap_or_default(),
+            created_at: n.created_at,
+            updated_at: n.updated_at,
+        }
+    }).collect();
+
+    let updated = items.iter()
+        .map(|i| i.updated_at)
+        .max()
+        .unwrap_or_else(|| chrono::DateTime::UNIX_EPOCH.naive_utc());
+
+    // The ETag covers everything the feed body depends on. There is no Last-Modified:
+    // renaming a category or tag, or editing the site description, changes the body
+    // without moving any note's updated_at
+    let mut fingerprint = format!(
+        "{}|{}|{}|{}|{}|{}",
+        path, params.join("&"), title, site.author, site.description, site.base_url
+    );
+    for i in &items {
+        fingerprint.push_str(&format!("|{}@{}|{}", i.id, i.updated_at, i.category.as_deref().unwrap_or("")));
+        for t in &i.tags {
+            fingerprint.push_str(&format!(",{}", t.title));
+        }
+    }
+
+    let self_path = if params.is_empty() { path.to_string() } else { format!("{}?{}", path, params.join("&")) };
+
+    Some(Feed {
+        etag: etag_for(fingerprint.as_bytes()),
+        site,
+        title,
+        self_path,
+        items,
+        updated,
+    })
+}
+
+fn respond(headers: &HeaderMap, feed: Option<Feed>, content_type: &'static str, body: impl FnOnce(&Feed) -> String) -> Response {
+    let Some(feed) = feed else {
+        return (StatusCode::NOT_FOUND, "Feeds are off until blogDomain is set").into_response();
+    };
+    let feed = &feed;
+    if is_not_modified(headers, &feed.etag, None) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, feed.etag.clone())]).into_response();
+    }
+
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::ETAG, feed.etag.clone()),
+        ],
+        body(feed),
+    ).into_response()
+}
+
+fn render_rss(feed: &Feed) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:content=\"http://purl.org/rss/1.0/modules/content/\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<channel>\n");
+    xml.push_str(&format!("<title>{}</title>\n", escape_html(&feed.title)));
+    xml.push_str(&format!("<link>{}</link>\n", escape_html(&feed.home_url())));
+    xml.push_str(&format!("<description>{}</description>\n", escape_html(&feed.site.description)));
+    xml.push_str("<language>zh-CN</language>\n");
+    xml.push_str(&format!("<lastBuildDate>{}</lastBuildDate>\n", feed.updated.and_utc().to_rfc2822()));
+    xml.push_str(&format!("<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n", escape_html(&feed.self_url())));
+
+    for item in &feed.items {
+        xml.push_str("<item>\n");
+        xml.push_str(&format!("<title>{}</title>\n", escape_html(&item.title)));
+        xml.push_str(&format!("<link>{}</link>\n", escape_html(&item.url)));
+        xml.push_str(&format!("<guid isPermaLink=\"true\">{}</guid>\n", escape_html(&item.url)));
+        xml.push_str(&format!("<pubDate>{}</pubDate>\n", item.created_at.and_utc().to_rfc2822()));
+        // RSS <author> must be an email address; the name goes in dc:creator
+        if !feed.site.author.is_empty() {
+            xml.push_str(&format!("<dc:creator>{}</dc:creator>\n", escape_html(&feed.site.author)));
+        }
+        if let Some(ref c) = item.category {
+            xml.push_str(&format!("<category>{}</category>\n", escape_html(c)));
+        }
+        for t in &item.tags {
+            xml.push_str(&format!("<category>{}</category>\n", escape_html(&t.title)));
+        }
+        xml.push_str(&format!("<description>{}</description>\n", escape_html(&item.summary)));
+        xml.push_str(&format!("<content:encoded>{}</content:encoded>\n", escape_html(&item.content_html)));
+        xml.push_str("</item>\n");
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn render_atom(feed: &Feed) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\" xml:lang=\"zh-CN\">\n");
+    xml.push_str(&format!("<id>{}</id>\n", escape_html(&feed.self_url())));
+    xml.push_str(&format!("<title>{}</title>\n", escape_html(&feed.title)));
+    if !feed.site.description.is_empty() {
+        xml.push_str(&format!("<subtitle>{}</subtitle>\n", escape_html(&feed.site.description)));
+    }
+    xml.push_str(&format!("<updated>{}</updated>\n", feed.updated.and_utc().to_rfc3339()));
+    xml.push_str(&format!("<link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>\n", escape_html(&feed.self_url())));
+    xml.push_str(&format!("<link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n", escape_html(&feed.home_url())));
+    xml.push_str(&format!("<author><name>{}</name></author>\n", escape_html(&feed.site.author)));
+
+    for item in &feed.items {
+        xml.push_str("<entry>\n");
+        xml.push_str(&format!("<id>{}</id>\n", escape_html(&item.url)));
+        xml.push_str(&format!("<title>{}</title>\n", escape_html(&item.title)));
+        xml.push_str(&format!("<link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n", escape_html(&item.url)));
+        xml.push_str(&format!("<published>{}</published>\n", item.created_at.and_utc().to_rfc3339()));
+        xml.push_str(&format!("<updated>{}</updated>\n", item.updated_at.and_utc().to_rfc3339()));
+        if let Some(ref c) = item.category {
+            xml.push_str(&format!("<category term=\"{}\"/>\n", escape_html(c)));
+        }
+        for t in &item.tags {
+            xml.push_str(&format!("<category term=\"{}\"/>\n", escape_html(&t.title)));
+        }
+        xml.push_str(&format!("<summary>{}</summary>\n", escape_html(&item.summary)));
+        xml.push_str(&format!("<content type=\"html\">{}</content>\n", escape_html(&item.content_html)));
+        xml.push_str("</entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+// JSON Feed 1.1, https://www.jsonfeed.org/version/1.1/
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: String,
+    feed_url: String,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    description: &'a str,
+    language: &'static str,
+    authors: Vec<JsonFeedAuthor<'a>>,
+    items: Vec<JsonFeedItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem<'a> {
+    id: String,
+    url: &'a str,
+    title: &'a str,
+    summary: &'a str,
+    content_html: &'a str,
+    date_published: String,
+    date_modified: String,
+    tags: Vec<&'a str>,
+}
+
+fn render_json_feed(feed: &Feed) -> String {
+    let json = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: &feed.title,
+        home_page_url: feed.home_url(),
+        feed_url: feed.self_url(),
+        description: &feed.site.description,
+        language: "zh-CN",
+        authors: vec![JsonFeedAuthor { name: &feed.site.author }],
+        items: feed.items.iter().map(|item| JsonFeedItem {
+            id: item.id.to_string(),
+            url: &item.url,
+            title: &item.title,
+            summary: &item.summary,
+            content_html: &item.content_html,
+            date_published: item.created_at.and_utc().to_rfc3339(),
+            date_modified: item.updated_at.and_utc().to_rfc3339(),
+            tags: item.category.iter().map(|c| c.as_str())
+                .chain(item.tags.iter().map(|t| t.title.as_str()))
+                .collect(),
+        }).collect(),
+    };
+    serde_json::to_string(&json).unwrap_or_default()
+}
+
+// GET /feed.xml
+pub async fn rss_feed(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FeedQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let feed = load_feed(&state, &query, "/feed.xml").await;
+    respond(&headers, feed, "application/rss+xml; charset=utf-8", render_rss)
+}
+
+// GET /atom.xml
+pub async fn atom_feed(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FeedQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let feed = load_feed(&state, &query, "/atom.xml").await;
+    respond(&headers, feed, "application/atom+xml; charset=utf-8", render_atom)
+}
+
+// GET /feed.json
+pub async fn json_feed(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FeedQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let feed = load_feed(&state, &query, "/feed.json").await;
+    respond(&headers, feed, "application/feed+json; charset=utf-8", render_json_feed)
+}
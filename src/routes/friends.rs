@@ -1,9 +1,12 @@
-use axum::{Json, extract::{State, Path}};
+use axum::{Json, extract::{State, Path}, http::HeaderMap};
 use sea_orm::{EntityTrait, ActiveModelTrait, Set, QueryFilter, ColumnTrait};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use crate::entity::friend;
+use crate::events::DomainEvent;
+use crate::middleware::is_admin;
 use crate::routes::AppState;
+use crate::spam::{SpamInput, Verdict};
 use crate::utils::ApiResponse;
 use serde_json::Value;
 
@@ -63,21 +66,63 @@ pub struct UpsertFriend {
     avatar: String,
     description: String,
     status: Option<i32>,
+    // Hidden field, must stay empty
+    #[serde(rename = "hp")]
+    honeypot: Option<String>,
+    #[serde(rename = "formToken")]
+    form_token: Option<String>,
 }
 
+// Shared by the public application form and the admin panel
 pub async fn create_friend(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(payload): Json<UpsertFriend>,
 ) -> Json<ApiResponse<String>> {
+    let admin = is_admin(&headers);
+    if !admin {
+        if let Err(msg) = state.captcha.verify_headers(&headers) {
+            return Json(ApiResponse { code: 428, message: msg.to_string(), data: String::new() });
+        }
+        let input = SpamInput {
+            author: &payload.name,
+            email: "",
+            website: Some(&payload.url),
+            content: &payload.description,
+            honeypot: payload.honeypot.as_deref(),
+            form_token: payload.form_token.as_deref(),
+        };
+        // Applications are moderated anyway, so only outright blocks are refused
+        match state.spam.evaluate(&state.db, &input).await {
+            Ok(Verdict::Ham) => {}
+            Ok(Verdict::Suspicious(reason)) => tracing::warn!("Suspicious friend application {}: {}", payload.url, reason),
+            Ok(Verdict::Block(reason)) => {
+                tracing::warn!("Friend application {} blocked: {}", payload.url, reason);
+                return Json(ApiResponse::error("Application rejected"));
+            }
+            Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+        }
+    }
+
+    // Visitors cannot approve their own link
+    let status = if admin { payload.status.or(Some(0)) } else { Some(0) };
     let f = friend::ActiveModel {
-        name: Set(payload.name),
-        link: Set(payload.url),
+        name: Set(payload.name.clone()),
+        link: Set(payload.url.clone()),
         avatar: Set(Some(payload.avatar)),
-        description: Set(Some(payload.description)),
-        status: Set(payload.status.or(Some(0))),
+        description: Set(Some(payload.description.clone())),
+        status: Set(status),
         ..Default::default()
     };
-    friend::Entity::insert(f).exec(&state.db).await.unwrap();
+    let res = friend::Entity::insert(f).exec(&state.db).await.unwrap();
+    if !admin {
+        state.events.publish(DomainEvent::FriendSubmitted {
+            id: res.last_insert_id,
+            name: payload.name,
+            url: payload.url,
+            description: payload.description,
+        });
+    }
     Json(ApiResponse::success("Created".to_string()))
 }
 
@@ -0,0 +1,131 @@
+use axum::{Json, extract::{Query, State}};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, QueryOrder, Condition, PaginatorTrait};
+use sea_orm::sea_query::Expr;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::entity::job;
+use crate::jobs::{self, STATUS_CANCELLED, STATUS_DEAD, STATUS_QUEUED};
+use crate::routes::AppState;
+use crate::utils::{ApiResponse, format_datetime};
+
+const JOB_PAGE_SIZE: u64 = 20;
+
+#[derive(Deserialize)]
+pub struct JobQuery {
+    pub status: Option<String>,
+    #[serde(rename = "type")]
+    pub job_type: Option<String>,
+    pub page: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct JobDto {
+    #[serde(rename = "jobKey")]
+    pub id: i32,
+    #[serde(rename = "type")]
+    pub job_type: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i32,
+    #[serde(rename = "maxAttempts")]
+    pub max_attempts: i32,
+    #[serde(rename = "uniqueKey")]
+    pub unique_key: Option<String>,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename = "runTime")]
+    pub run_at: String,
+    #[serde(rename = "finishTime")]
+    pub finished_at: Option<String>,
+    #[serde(rename = "createTime")]
+    pub created_at: String,
+}
+
+fn map_job(j: job::Model) -> JobDto {
+    JobDto {
+        id: j.id,
+        job_type: j.job_type,
+        payload: j.payload,
+        status: j.status,
+        attempts: j.attempts,
+        max_attempts: j.max_attempts,
+        unique_key: j.unique_key,
+        last_error: j.last_error,
+        run_at: format_datetime(j.run_at),
+        finished_at: j.finished_at.map(format_datetime),
+        created_at: format_datetime(j.created_at),
+    }
+}
+
+// GET /api/protected/jobs?status=dead&type=email.send&page=1
+pub async fn list_jobs(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<JobQuery>,
+) -> Json<ApiResponse<Vec<JobDto>>> {
+    let mut condition = Condition::all();
+    if let Some(status) = query.status.filter(|s| !s.is_empty()) {
+        condition = condition.add(job::Column::Status.eq(status));
+    }
+    if let Some(job_type) = query.job_type.filter(|t| !t.is_empty()) {
+        condition = condition.add(job::Column::JobType.eq(job_type));
+    }
+
+    let found = job::Entity::find()
+        .filter(condition)
+        .order_by_desc(job::Column::Id)
+        .paginate(&state.db, JOB_PAGE_SIZE)
+        .fetch_page(query.page.unwrap_or(1).max(1) - 1)
+        .await;
+
+    match found {
+        Ok(j) => Json(ApiResponse::success(j.into_iter().map(map_job).collect())),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+// POST /api/protected/jobs/retry, dead or cancelled jobs run again with a fresh retry budget
+pub async fn retry_jobs(
+    State(state): State<Arc<AppState>>,
+    Json(keys): Json<Vec<i32>>,
+) -> Json<ApiResponse<String>> {
+    let res = job::Entity::update_many()
+        .col_expr(job::Column::Status, Expr::value(STATUS_QUEUED))
+        .col_expr(job::Column::Attempts, Expr::value(0))
+        .col_expr(job::Column::RunAt, Expr::value(chrono::Utc::now().naive_utc()))
+        .col_expr(job::Column::FinishedAt, Expr::value(Option::<chrono::NaiveDateTime>::None))
+        .col_expr(job::Column::UpdatedAt, Expr::value(chrono::Utc::now().naive_utc()))
+        .filter(job::Column::Id.is_in(keys))
+        .filter(job::Column::Status.is_in([STATUS_DEAD, STATUS_CANCELLED]))
+        .exec(&state.db)
+        .await;
+
+    match res {
+        Ok(r) => {
+            jobs::wake(&state);
+            Json(ApiResponse::success(format!("Queued {} jobs", r.rows_affected)))
+        }
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+// POST /api/protected/jobs/cancel, only jobs that have not started yet
+pub async fn cancel_jobs(
+    State(state): State<Arc<AppState>>,
+    Json(keys): Json<Vec<i32>>,
+) -> Json<ApiResponse<String>> {
+    let now = chrono::Utc::now().naive_utc();
+    let res = job::Entity::update_many()
+        .col_expr(job::Column::Status, Expr::value(STATUS_CANCELLED))
+        .col_expr(job::Column::UniqueKey, Expr::value(Option::<String>::None))
+        .col_expr(job::Column::FinishedAt, Expr::value(now))
+        .col_expr(job::Column::UpdatedAt, Expr::value(now))
+        .filter(job::Column::Id.is_in(keys))
+        .filter(job::Column::Status.eq(STATUS_QUEUED))
+        .exec(&state.db)
+        .await;
+
+    match res {
+        Ok(r) => Json(ApiResponse::success(format!("Cancelled {} jobs", r.rows_affected))),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
@@ -6,6 +6,18 @@ pub mod friends;
 pub mod web_info;
 pub mod talks;
 pub mod upload;
+pub mod revisions;
+pub mod series;
+pub mod related;
+pub mod feeds;
+pub mod sitemap;
+pub mod prerender;
+pub mod archive;
+pub mod stats;
+pub mod reactions;
+pub mod comments;
+pub mod webhooks;
+pub mod jobs;
 
 use axum::{
     routing::{get, post, delete, put},
@@ -13,46 +25,129 @@ use axum::{
     middleware,
 };
 use sea_orm::DatabaseConnection;
-use tower_http::{cors::{Any, CorsLayer}, services::ServeDir};
+use std::sync::Arc;
+use crate::markdown::RenderCache;
+use crate::views::ViewTracker;
+use crate::rate_limit::RateLimiter;
+use crate::spam::SpamFilter;
+use crate::captcha::Captcha;
+use crate::jobs::JobQueue;
+use crate::response_cache::{self, ResponseCache};
+use crate::webhooks::Webhooks;
+use crate::events::EventBus;
+use std::time::Duration;
+use tower_http::{compression::CompressionLayer, cors::{Any, CorsLayer}, services::ServeDir};
 
 pub struct AppState {
     pub db: DatabaseConnection,
+    pub render_cache: RenderCache,
+    pub views: ViewTracker,
+    pub reaction_limiter: RateLimiter,
+    pub comment_limiter: RateLimiter,
+    pub spam: SpamFilter,
+    pub captcha: Captcha,
+    // Failed logins per IP; past the limit the login form needs a captcha
+    pub login_failures: RateLimiter,
+    pub jobs: JobQueue,
+    pub response_cache: ResponseCache,
+    pub webhooks: Webhooks,
+    pub events: EventBus,
 }
 
-pub fn create_router(state: AppState) -> Router {
+impl AppState {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self {
+            db,
+            render_cache: RenderCache::default(),
+            views: ViewTracker::default(),
+            reaction_limiter: RateLimiter::new(20, Duration::from_secs(60)),
+            comment_limiter: RateLimiter::new(5, Duration::from_secs(600)),
+            spam: SpamFilter::default(),
+            captcha: Captcha::default(),
+            login_failures: RateLimiter::new(3, Duration::from_secs(900)),
+            jobs: JobQueue::default(),
+            response_cache: ResponseCache::default(),
+            webhooks: Webhooks::default(),
+            events: EventBus::default(),
+        }
+    }
+}
+
+pub fn create_router(state: impl Into<Arc<AppState>>) -> Router {
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any);
     
-    let state_arc = std::sync::Arc::new(state);
+    let state_arc: Arc<AppState> = state.into();
 
     let public_routes = Router::new()
         // Auth
         .route("/api/login", post(auth::login))
+
+        // Proof-of-work challenge, solved answers go in the X-Captcha header
+        .route("/api/public/captcha", get(auth::captcha_challenge))
         
         // Public Notes
-        .route("/api/public/notes", get(notes::list_public_notes))
-        .route("/api/public/notes/page", get(notes::list_public_notes)) 
         .route("/api/public/notes/search", post(notes::search_notes))
         .route("/api/public/notes/:id", get(notes::get_note_detail))
-        .route("/api/public/topnotes", get(notes::get_top_notes))
+        .route("/api/public/notes/by-slug/:slug", get(notes::get_note_by_slug))
+        .route("/api/public/highlight.css", get(notes::highlight_css))
+        .route("/api/public/archive", get(archive::get_archive))
+        
+        // Series
+        .route("/api/public/series/:id", get(series::get_public_series))
+
+        // Friends
+        .route("/api/friends", get(friends::list_friends)) 
+        .route("/api/public/friends", get(friends::list_public_friends)
+            .post(friends::create_friend))
         
+        // Reactions (notes and talks)
+        .route("/api/public/reactions", get(reactions::get_reactions)
+            .post(reactions::toggle_reaction))
+
+        // Comments (approved threads only)
+        .route("/api/public/comments", get(comments::list_public_comments)
+            .post(comments::create_comment))
+
+        // Signed token that public forms send back, used to measure fill time
+        .route("/api/public/form-token", get(comments::form_token))
+
+        // Feeds (?category=ID / ?tag=ID for a filtered feed)
+        .route("/feed.xml", get(feeds::rss_feed))
+        .route("/atom.xml", get(feeds::atom_feed))
+        .route("/feed.json", get(feeds::json_feed))
+
+        // Crawlers
+        .route("/sitemap.xml", get(sitemap::sitemap))
+        .route("/sitemaps/:page", get(sitemap::sitemap_page))
+        .route("/robots.txt", get(sitemap::robots))
+        .route("/prerender/article/:key", get(prerender::note_snapshot))
+        
+        // Static Image Download (Public)
+        .nest_service("/api/protect/download", ServeDir::new("/opt/memory_blog_rust/uploads"))
+        .nest_service("/christmas", ServeDir::new("/opt/memory_blog_rust/static/christmas"));
+
+    // Hot read-only endpoints served from the response cache. Note detail stays
+    // out because every hit has to count as a page view.
+    let cached_routes = Router::new()
+        // Public Notes
+        .route("/api/public/notes", get(notes::list_public_notes))
+        .route("/api/public/notes/page", get(notes::list_public_notes)) 
+        .route("/api/public/topnotes", get(notes::get_top_notes))
+
         // Categories
         .route("/api/category", get(categories::list_categories)) 
         .route("/api/public/category", get(categories::list_categories)) 
+        .route("/api/public/category/tree", get(categories::list_category_tree))
         
         // Tags
         .route("/api/tagone", get(tags::list_tags_one)) 
         .route("/api/tagtwo", get(tags::list_tags_two)) 
         .route("/api/public/tagone", get(tags::list_tags_one)) 
         .route("/api/public/tagtwo", get(tags::list_tags_two)) 
-        
-        // Friends
-        .route("/api/friends", get(friends::list_friends)) 
-        .route("/api/public/friends", get(friends::list_public_friends)
-            .post(friends::create_friend))
-        
+
         // Talks
         .route("/api/talk", get(talks::list_talks))
         .route("/api/public/talk", get(talks::list_talks))
@@ -60,10 +155,8 @@ pub fn create_router(state: AppState) -> Router {
         // Web/User Public
         .route("/api/public/user", get(web_info::get_user_info))
         .route("/api/public/social", get(web_info::get_social_info))
-        
-        // Static Image Download (Public)
-        .nest_service("/api/protect/download", ServeDir::new("/opt/memory_blog_rust/uploads"))
-        .nest_service("/christmas", ServeDir::new("/opt/memory_blog_rust/static/christmas"));
+
+        .route_layer(middleware::from_fn_with_state(state_arc.clone(), response_cache::serve_cached));
 
     let protected_routes = Router::new()
         // Images
@@ -84,11 +177,58 @@ pub fn create_router(state: AppState) -> Router {
             post(notes::update_note) 
         )
 
+        // Reactions
+        .route("/api/protected/reactions", delete(reactions::reset_reactions))
+
+        // Comment moderation
+        .route("/api/protected/comments",
+            get(comments::list_all_comments)
+            .delete(comments::delete_comments)
+        )
+        .route("/api/protected/comments/approve", post(comments::approve_comments))
+        .route("/api/protected/comments/reject", post(comments::reject_comments))
+
+        // Outgoing webhooks
+        .route("/api/protected/webhooks",
+            get(webhooks::list_webhooks)
+            .post(webhooks::create_webhook)
+            .delete(webhooks::delete_webhooks)
+        )
+        .route("/api/protected/webhooks/events", get(webhooks::list_events))
+        .route("/api/protected/webhooks/deliveries", get(webhooks::list_deliveries))
+        .route("/api/protected/webhooks/deliveries/:id/redeliver", post(webhooks::redeliver))
+        .route("/api/protected/webhooks/:id", post(webhooks::update_webhook))
+
+        // Background jobs
+        .route("/api/protected/jobs", get(jobs::list_jobs))
+        .route("/api/protected/jobs/retry", post(jobs::retry_jobs))
+        .route("/api/protected/jobs/cancel", post(jobs::cancel_jobs))
+
+        // Dashboard statistics
+        .route("/api/protected/stats", get(stats::get_stats))
+        .route("/api/protected/stats/views", get(stats::get_view_summary))
+
+        // Note Revisions
+        .route("/api/protected/notes/:id/revisions", get(revisions::list_revisions))
+        .route("/api/protected/notes/:id/revisions/diff", get(revisions::diff_revisions))
+        .route("/api/protected/notes/:id/revisions/:rev_id", get(revisions::get_revision))
+        .route("/api/protected/notes/:id/revisions/:rev_id/restore", post(revisions::restore_revision))
+
+        // Series
+        .route("/api/protected/series",
+            get(series::list_series)
+            .post(series::create_series)
+            .delete(series::delete_series)
+        )
+        .route("/api/protected/series/:id", post(series::update_series))
+
         // Categories
         .route("/api/protected/category", 
-             post(categories::create_category)
+             get(categories::list_admin_categories)
+             .post(categories::create_category)
              .delete(categories::delete_category) 
         )
+        .route("/api/protected/category/reorder", post(categories::reorder_categories))
         .route("/api/protected/category/:id", 
              post(categories::update_category)
         )
@@ -125,10 +265,15 @@ pub fn create_router(state: AppState) -> Router {
         )
         .route("/api/protected/social", put(web_info::update_social_info))
         
+        .route_layer(middleware::from_fn_with_state(state_arc.clone(), response_cache::invalidate_on_write))
         .route_layer(middleware::from_fn(crate::middleware::auth_guard));
 
     public_routes
+        .merge(cached_routes)
         .merge(protected_routes)
+        .layer(middleware::from_fn(crate::middleware::conditional_get))
+        // gzip / br / zstd, whichever the client prefers; tiny and image bodies are left as they are
+        .layer(CompressionLayer::new())
         .layer(cors)
         .with_state(state_arc)
 }
@@ -1,15 +1,30 @@
-use axum::{Json, extract::{State, Query, Path}};
-use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, QueryOrder, Condition, ActiveModelTrait, Set, PaginatorTrait};
+use axum::{Json, extract::{State, Query, Path, ConnectInfo}};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, QueryOrder, Condition, ActiveModelTrait, Set, PaginatorTrait, DbErr, TransactionTrait};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use crate::entity::{note, category};
-use crate::routes::AppState;
-use crate::utils::ApiResponse;
+use crate::entity::{note, category, note_slug_redirect};
+use crate::routes::{AppState, categories, comments, prerender, reactions, related, revisions, series, tags};
+use crate::routes::reactions::ReactionCounts;
+use crate::utils::{ApiResponse, format_datetime, parse_datetime};
+use crate::markdown::{self, TocEntry};
+use axum::http::{header, HeaderMap};
+use std::net::SocketAddr;
+use axum::response::{IntoResponse, Redirect, Response};
+use crate::{slug, views};
+use crate::events::DomainEvent;
+use crate::routes::categories::CategoryCrumbDto;
+use crate::routes::tags::NoteTagDto;
+use crate::routes::series::SeriesContextDto;
+use crate::routes::related::NoteLinkDto;
+use sea_orm::ConnectionTrait;
+use std::collections::HashMap;
 
 #[derive(Deserialize)]
 pub struct NoteQuery {
     pub category_id: Option<i32>,
     pub page: Option<u64>,
+    pub tag_id: Option<i32>,
+    pub tag_level: Option<i32>, // 1 or 2, only needed if the id is ambiguous
 }
 
 #[derive(Serialize)]
@@ -46,10 +61,35 @@ pub struct NoteDto {
     pub category_id: Option<i32>, 
     #[serde(rename = "categoryTitle")]
     pub category_title: Option<String>,
+    // Root category first, ending with the note's own category
+    pub breadcrumb: Vec<CategoryCrumbDto>,
     
     pub is_public: bool,
     #[serde(rename = "noteTags")]
     pub tags: String, 
+    #[serde(rename = "tagList")]
+    pub tag_list: Vec<NoteTagDto>,
+
+    #[serde(rename = "publishAt")]
+    pub publish_at: Option<String>,
+    pub slug: Option<String>,
+    #[serde(rename = "viewCount")]
+    pub view_count: i32,
+    pub reactions: ReactionCounts,
+
+    // Server-rendered content, only filled on the detail endpoint
+    #[serde(rename = "contentHtml", skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toc: Option<Vec<TocEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series: Option<SeriesContextDto>,
+    #[serde(rename = "prevNote", skip_serializing_if = "Option::is_none")]
+    pub prev_note: Option<NoteLinkDto>,
+    #[serde(rename = "nextNote", skip_serializing_if = "Option::is_none")]
+    pub next_note: Option<NoteLinkDto>,
+    #[serde(rename = "relatedNotes", skip_serializing_if = "Option::is_none")]
+    pub related_notes: Option<Vec<NoteLinkDto>>,
 }
 
 fn map_note(n: note::Model, cat: Option<category::Model>) -> NoteDto {
@@ -70,24 +110,84 @@ fn map_note(n: note::Model, cat: Option<category::Model>) -> NoteDto {
         status: n.status.unwrap_or("published".to_string()),
         category_id: cat_id,
         category_title: cat_name,
+        breadcrumb: vec![],
         is_public: n.is_public,
         tags: n.tags.unwrap_or_default(),
+        tag_list: vec![],
+        publish_at: n.publish_at.map(format_datetime),
+        slug: n.slug,
+        view_count: n.view_count,
+        reactions: ReactionCounts::new(),
+        content_html: None,
+        toc: None,
+        series: None,
+        prev_note: None,
+        next_note: None,
+        related_notes: None,
     }
 }
 
+/// Fills `tag_list` from the note_tag relation, the reaction counts and the
+/// category breadcrumb of each note.
+async fn enrich_note_dtos<C: ConnectionTrait>(db: &C, mut dtos: Vec<NoteDto>) -> Vec<NoteDto> {
+    let ids: Vec<i32> = dtos.iter().map(|d| d.id).collect();
+    let mut by_note = tags::load_note_tags(db, &ids).await.unwrap_or_default();
+    let mut reaction_counts = reactions::load_counts(db, reactions::TARGET_NOTE, &ids).await.unwrap_or_default();
+    let cats = if dtos.iter().any(|d| d.category_id.is_some()) {
+        categories::load_all(db).await.unwrap_or_default()
+    } else {
+        vec![]
+    };
+    let cats_by_id: HashMap<i32, &category::Model> = cats.iter().map(|c| (c.id, c)).collect();
+    for dto in dtos.iter_mut() {
+        dto.tag_list = by_note.remove(&dto.id).unwrap_or_default();
+        dto.reactions = reaction_counts.remove(&dto.id).unwrap_or_default();
+        if let Some(cat_id) = dto.category_id {
+            dto.breadcrumb = categories::breadcrumb(&cats_by_id, cat_id);
+        }
+    }
+    dtos
+}
+
+/// Visibility rules shared by every public note query: drafts, private and
+/// not-yet-published scheduled notes are never returned.
+pub(crate) fn public_condition() -> Condition {
+    Condition::all()
+        .add(note::Column::IsPublic.eq(true))
+        .add(note::Column::Status.is_not_in(["draft", "scheduled"]))
+}
+
+/// `public_condition` for a note that is already loaded.
+pub(crate) fn is_visible(n: &note::Model) -> bool {
+    n.is_public && !matches!(n.status.as_deref(), Some("draft") | Some("scheduled"))
+}
+
+/// Note fields sent with `note.*` webhook events.
+pub(crate) fn webhook_data(n: &note::Model) -> serde_json::Value {
+    serde_json::json!({
+        "noteKey": n.id,
+        "noteTitle": n.title,
+        "slug": n.slug,
+        "status": n.status,
+        "isPublic": is_visible(n),
+        "path": format!("/article/{}", n.id),
+    })
+}
+
 pub async fn list_public_notes(
     State(state): State<Arc<AppState>>,
     Query(query): Query<NoteQuery>,
 ) -> Json<ApiResponse<Vec<NoteDto>>> {
-    let mut condition = Condition::all();
+    // STRICT FILTER FOR PUBLIC API
+    let mut condition = public_condition();
 
     if let Some(cat_id) = query.category_id {
-        condition = condition.add(note::Column::CategoryId.eq(cat_id));
+        condition = condition.add(categories::subtree_filter(&state.db, cat_id).await);
     }
 
-    // STRICT FILTER FOR PUBLIC API
-    condition = condition.add(note::Column::IsPublic.eq(true));
-    condition = condition.add(note::Column::Status.ne("draft"));
+    if let Some(tag_id) = query.tag_id {
+        condition = condition.add(tags::tag_filter(tag_id, query.tag_level));
+    }
 
     // PAGINATION LOGIC
     let page = query.page.unwrap_or(1);
@@ -104,9 +204,12 @@ pub async fn list_public_notes(
         .await
         .unwrap_or(vec![]);
 
+    // No Last-Modified here: deleting or unpublishing a note changes the list without
+    // touching any updated_at in it, so only the body ETag can tell
     let dtos = notes.into_iter().map(|(n, cat)| {
         map_note(n, cat)
     }).collect();
+    let dtos = enrich_note_dtos(&state.db, dtos).await;
 
     Json(ApiResponse::success(dtos))
 }
@@ -126,6 +229,7 @@ pub async fn list_all_notes(
     let dtos = notes.into_iter().map(|(n, cats)| {
         map_note(n, cats.into_iter().next())
     }).collect();
+    let dtos = enrich_note_dtos(&state.db, dtos).await;
 
     Json(ApiResponse::success(dtos))
 }
@@ -145,18 +249,16 @@ pub async fn search_notes(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<SearchRequest>,
 ) -> Json<ApiResponse<Vec<NoteDto>>> {
-    let mut condition = Condition::all();
-
     // PUBLIC SAFEGUARDS
-    condition = condition.add(note::Column::IsPublic.eq(true));
-    condition = condition.add(note::Column::Status.ne("draft"));
+    let mut condition = public_condition();
 
     if let Some(ref k) = payload.keyword {
          if !k.is_empty() {
              condition = condition.add(
                 Condition::any()
                     .add(note::Column::Title.contains(k))
-                    .add(note::Column::Content.contains(k)).add(note::Column::Tags.contains(k))
+                    .add(note::Column::Content.contains(k))
+                    .add(tags::tag_name_filter(k))
              );
          }
     }
@@ -169,7 +271,7 @@ pub async fn search_notes(
             .unwrap_or(None);
             
         if let Some(c) = cat_model {
-            condition = condition.add(note::Column::CategoryId.eq(c.id));
+            condition = condition.add(categories::subtree_filter(&state.db, c.id).await);
         } else {
              return Json(ApiResponse::success(vec![]));
         }
@@ -188,6 +290,7 @@ pub async fn search_notes(
     let dtos = notes.into_iter().map(|(n, cats)| {
         map_note(n, cats.into_iter().next())
     }).collect();
+    let dtos = enrich_note_dtos(&state.db, dtos).await;
 
     Json(ApiResponse::success(dtos))
 }
@@ -205,7 +308,8 @@ pub async fn search_all_notes(
              condition = condition.add(
                 Condition::any()
                     .add(note::Column::Title.contains(k))
-                    .add(note::Column::Content.contains(k)).add(note::Column::Tags.contains(k))
+                    .add(note::Column::Content.contains(k))
+                    .add(tags::tag_name_filter(k))
              );
          }
     }
@@ -218,7 +322,7 @@ pub async fn search_all_notes(
             .unwrap_or(None);
             
         if let Some(c) = cat_model {
-            condition = condition.add(note::Column::CategoryId.eq(c.id));
+            condition = condition.add(categories::subtree_filter(&state.db, c.id).await);
         } else {
              return Json(ApiResponse::success(vec![]));
         }
@@ -258,6 +362,7 @@ pub async fn search_all_notes(
     let dtos = notes.into_iter().map(|(n, cats)| {
         map_note(n, cats.into_iter().next())
     }).collect();
+    let dtos = enrich_note_dtos(&state.db, dtos).await;
 
     Json(ApiResponse::success(dtos))
 }
@@ -280,17 +385,21 @@ pub struct UpsertNoteRequest {
     
     #[serde(rename = "noteTags")]
     pub tags: Option<String>,
+    // Preferred over noteTags when both are sent; each entry is `{id, level}` or `"level:id"`
+    #[serde(rename = "tagIds")]
+    pub tag_ids: Option<Vec<tags::TagRef>>,
     
     pub is_public: Option<bool>, 
+
+    #[serde(rename = "publishAt")]
+    pub publish_at: Option<String>,
+    pub slug: Option<String>,
 }
 
 pub async fn get_top_notes(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<Vec<NoteDto>>> {
-    let mut condition = Condition::all();
-    condition = condition.add(note::Column::IsTop.eq(1));
-    condition = condition.add(note::Column::IsPublic.eq(true));
-    condition = condition.add(note::Column::Status.ne("draft"));
+    let condition = public_condition().add(note::Column::IsTop.eq(1));
 
     let notes = note::Entity::find()
         .filter(condition)
@@ -302,6 +411,7 @@ pub async fn get_top_notes(
      let dtos = notes.into_iter().map(|(n, cats)| {
         map_note(n, cats.into_iter().next())
     }).collect();
+    let dtos = enrich_note_dtos(&state.db, dtos).await;
 
     Json(ApiResponse::success(dtos))
 }
@@ -317,10 +427,37 @@ pub async fn create_note(
     let mut is_public = payload.is_public.unwrap_or(true);
     let status_str = payload.status.clone().unwrap_or("published".to_string());
     
-    if status_str == "draft" || status_str == "private" {
+    if status_str == "draft" || status_str == "private" || status_str == "scheduled" {
         is_public = false;
     }
+
+    let publish_at = match payload.publish_at.as_deref() {
+        None | Some("") => None,
+        Some(v) => match parse_datetime(v) {
+            Some(dt) => Some(dt),
+            None => return Json(ApiResponse::error("Invalid publishAt")),
+        },
+    };
+    if status_str == "scheduled" && publish_at.is_none() {
+        return Json(ApiResponse::error("publishAt is required for scheduled notes"));
+    }
     
+    let slug_result = match payload.slug.as_deref().filter(|v| !v.trim().is_empty()) {
+        Some(v) => {
+            let wanted = slug::slugify(v);
+            match slug::is_taken(&state.db, &wanted, None).await {
+                Ok(false) => Ok(wanted),
+                Ok(true) => return Json(ApiResponse::error("Slug already in use")),
+                Err(e) => Err(e),
+            }
+        }
+        None => slug::unique_slug(&state.db, &title, None).await,
+    };
+    let note_slug = match slug_result {
+        Ok(v) => v,
+        Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+    };
+
     let new_note = note::ActiveModel {
         title: Set(title),
         content: Set(content),
@@ -332,14 +469,36 @@ pub async fn create_note(
         status: Set(Some(status_str)),
         created_at: Set(chrono::Utc::now().naive_utc()),
         updated_at: Set(chrono::Utc::now().naive_utc()),
-        tags: Set(payload.tags),
+        tags: Set(payload.tags.clone()),
+        publish_at: Set(publish_at),
+        slug: Set(Some(note_slug)),
         ..Default::default()
     };
 
-    match new_note.insert(&state.db).await {
-        Ok(_) => Json(ApiResponse::success("Note created successfully".to_string())),
-        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    let created = match new_note.insert(&state.db).await {
+        Ok(n) => n,
+        Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+    };
+    let mut created_note = created.clone();
+
+    let tag_ids = payload.tag_ids.or_else(|| payload.tags.as_deref().map(tags::parse_tag_refs));
+    if let Some(ids) = tag_ids {
+        // Keep the legacy column in sync with what actually got linked
+        let res = match tags::set_note_tags(&state.db, created.id, &ids).await {
+            Ok(joined) => {
+                let mut active: note::ActiveModel = created.into();
+                active.tags = Set(Some(joined));
+                active.update(&state.db).await.map(|n| created_note = n)
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            return Json(ApiResponse::error(&format!("Error: {}", e)));
+        }
     }
+
+    state.events.publish(DomainEvent::NoteCreated(created_note));
+    Json(ApiResponse::success("Note created successfully".to_string()))
 }
 
 pub async fn update_note(
@@ -350,7 +509,46 @@ pub async fn update_note(
     let note_data = note::Entity::find_by_id(id).one(&state.db).await.unwrap_or(None);
     
     if let Some(n) = note_data {
+        let publish_at = match payload.publish_at.as_deref() {
+            Some("") => None,
+            Some(v) => match parse_datetime(v) {
+                Some(dt) => Some(dt),
+                None => return Json(ApiResponse::error("Invalid publishAt")),
+            },
+            None => n.publish_at,
+        };
+        if payload.status.as_deref() == Some("scheduled") && publish_at.is_none() {
+            return Json(ApiResponse::error("publishAt is required for scheduled notes"));
+        }
+
+        // Slugs stay stable across title edits; they only change when set explicitly
+        let new_slug = match payload.slug.as_deref().filter(|v| !v.trim().is_empty()) {
+            Some(v) => {
+                let wanted = slug::slugify(v);
+                match slug::is_taken(&state.db, &wanted, Some(id)).await {
+                    Ok(false) => Some(wanted),
+                    Ok(true) => return Json(ApiResponse::error("Slug already in use")),
+                    Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+                }
+            }
+            None if n.slug.is_none() => {
+                let title = payload.title.as_deref().unwrap_or(&n.title);
+                match slug::unique_slug(&state.db, title, Some(id)).await {
+                    Ok(v) => Some(v),
+                    Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+                }
+            }
+            None => None,
+        };
+
+        let renamed = match (n.slug.clone(), new_slug.clone()) {
+            (Some(old), Some(new)) if old != new => Some((old, new)),
+            _ => None,
+        };
+        let previous = n.clone();
+        let was_visible = is_visible(&n);
         let mut active_model: note::ActiveModel = n.into();
+        if let Some(v) = new_slug { active_model.slug = Set(Some(v)); }
         
         if let Some(v) = payload.title { active_model.title = Set(v); }
         if let Some(v) = payload.content { active_model.content = Set(v); }
@@ -359,14 +557,15 @@ pub async fn update_note(
         if let Some(v) = payload.description { active_model.description = Set(Some(v)); }
         if let Some(v) = payload.cover { active_model.cover = Set(Some(v)); }
         if let Some(v) = payload.is_top { active_model.is_top = Set(Some(v)); }
-        if let Some(v) = payload.tags { active_model.tags = Set(Some(v)); }
+        let tag_ids = payload.tag_ids.or_else(|| payload.tags.as_deref().map(tags::parse_tag_refs));
+        active_model.publish_at = Set(publish_at);
         
         // Handle Status and Visibility logic
         if let Some(v) = payload.status.clone() { 
             active_model.status = Set(Some(v.clone()));
             if v == "public" || v == "published" {
                  active_model.is_public = Set(true);
-            } else if v == "private" || v == "draft" {
+            } else if v == "private" || v == "draft" || v == "scheduled" {
                  active_model.is_public = Set(false);
             }
         }
@@ -376,15 +575,33 @@ pub async fn update_note(
         
         // Double check consistency if status was updated
         if let Some(status_val) = payload.status {
-             if status_val == "draft" || status_val == "private" {
+             if status_val == "draft" || status_val == "private" || status_val == "scheduled" {
                  active_model.is_public = Set(false);
              }
         }
 
         active_model.updated_at = Set(chrono::Utc::now().naive_utc());
-        
-        match active_model.update(&state.db).await {
-            Ok(_) => Json(ApiResponse::success("Note updated successfully".to_string())),
+
+        // The revision, slug redirect and tags only stick if the note itself saves
+        let saved = async {
+            let txn = state.db.begin().await?;
+            revisions::snapshot(&txn, &previous).await?;
+            if let Some((old, new)) = &renamed {
+                slug::record_rename(&txn, id, old, new).await?;
+            }
+            if let Some(ids) = &tag_ids {
+                active_model.tags = Set(Some(tags::set_note_tags(&txn, id, ids).await?));
+            }
+            let updated = active_model.update(&txn).await?;
+            txn.commit().await?;
+            Ok::<_, DbErr>(updated)
+        }.await;
+
+        match saved {
+            Ok(updated) => {
+                state.events.publish(DomainEvent::NoteUpdated { note: updated, was_visible });
+                Json(ApiResponse::success("Note updated successfully".to_string()))
+            }
             Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
         }
     } else {
@@ -396,11 +613,29 @@ pub async fn delete_note(
     State(state): State<Arc<AppState>>,
     Json(keys): Json<Vec<i32>>,
 ) -> Json<ApiResponse<String>> {
+    if let Err(e) = reactions::delete_for(&state.db, reactions::TARGET_NOTE, &keys).await {
+        return Json(ApiResponse::error(&format!("Error: {}", e)));
+    }
+    if let Err(e) = comments::delete_for(&state.db, comments::TARGET_NOTE, &keys).await {
+        return Json(ApiResponse::error(&format!("Error: {}", e)));
+    }
+
+    // Looked up first so subscribers can still see which notes went away
+    let deleted = match note::Entity::find().filter(note::Column::Id.is_in(keys.clone())).all(&state.db).await {
+        Ok(n) => n,
+        Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+    };
+
     match note::Entity::delete_many()
         .filter(note::Column::Id.is_in(keys))
         .exec(&state.db)
         .await {
-        Ok(_) => Json(ApiResponse::success("Deleted".to_string())),
+        Ok(_) => {
+            for n in deleted {
+                state.events.publish(DomainEvent::NoteDeleted(n));
+            }
+            Json(ApiResponse::success("Deleted".to_string()))
+        }
         Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
     }
 }
@@ -408,16 +643,107 @@ pub async fn delete_note(
 pub async fn get_note_detail(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i32>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
 ) -> Json<ApiResponse<Option<NoteDto>>> {
-    let res = note::Entity::find_by_id(id)
+    // Drafts, private and not-yet-due scheduled notes are only for the admin
+    let mut select = note::Entity::find_by_id(id);
+    if !crate::middleware::is_admin(&headers) {
+        select = select.filter(public_condition());
+    }
+    let res = select
         .find_with_related(category::Entity)
         .all(&state.db)
         .await
         .unwrap_or(vec![]);
     
-    let dto = res.into_iter().next().map(|(n, cats)| {
-        map_note(n, cats.into_iter().next())
-    });
+    // No Last-Modified: reactions, views, neighbours and related notes all change
+    // the body without touching updated_at, so only the body ETag can tell
+    let dto = match res.into_iter().next() {
+        Some((n, cats)) => {
+            record_view(&state, &n, &headers, peer.map(|p| p.0));
+            Some(detail_dto(&state, n, cats.into_iter().next()).await)
+        }
+        None => None,
+    };
 
     Json(ApiResponse::success(dto))
+}
+
+/// Counts a reader's view. The admin previewing notes, crawlers and
+/// non-public notes are left out.
+fn record_view(state: &Arc<AppState>, n: &note::Model, headers: &HeaderMap, peer: Option<SocketAddr>) {
+    let status = n.status.as_deref().unwrap_or("published");
+    if !n.is_public || status == "draft" || status == "scheduled" || crate::middleware::is_admin(headers) {
+        return;
+    }
+    let user_agent = headers.get(header::USER_AGENT).and_then(|h| h.to_str().ok()).unwrap_or("");
+    if prerender::is_crawler(user_agent) {
+        return;
+    }
+
+    let ip = views::client_ip(headers, peer);
+    if state.views.record(n.id, &ip, user_agent) >= views::FLUSH_THRESHOLD {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = views::flush(&state).await {
+                tracing::error!("Flushing page views failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Full note for the detail views, including the server-rendered content.
+async fn detail_dto(state: &AppState, n: note::Model, cat: Option<category::Model>) -> NoteDto {
+    let rendered = state.render_cache.get_or_render(n.id, n.updated_at, &n.content);
+    let (prev, next) = related::neighbours(&state.db, &n).await.unwrap_or((None, None));
+    let related_notes = related::related_notes(&state.db, &n).await.unwrap_or_default();
+
+    let mut dto = map_note(n, cat);
+    dto.prev_note = prev;
+    dto.next_note = next;
+    dto.related_notes = Some(related_notes);
+    dto.content_html = Some(rendered.html.clone());
+    dto.toc = Some(rendered.toc.clone());
+    dto.series = series::context_for_note(&state.db, dto.id).await.unwrap_or(None);
+    enrich_note_dtos(&state.db, vec![dto]).await.remove(0)
+}
+
+pub async fn get_note_by_slug(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> Response {
+    let res = note::Entity::find()
+        .filter(public_condition().add(note::Column::Slug.eq(&slug)))
+        .find_with_related(category::Entity)
+        .all(&state.db)
+        .await
+        .unwrap_or(vec![]);
+
+    if let Some((n, cats)) = res.into_iter().next() {
+        let dto = detail_dto(&state, n, cats.into_iter().next()).await;
+        return Json(ApiResponse::success(Some(dto))).into_response();
+    }
+
+    // Old slug after a rename: send the reader to the current permalink, as long as
+    // that note is public; a draft's or private note's new slug stays hidden
+    let redirect = note_slug_redirect::Entity::find()
+        .filter(note_slug_redirect::Column::Slug.eq(&slug))
+        .find_also_related(note::Entity)
+        .filter(public_condition())
+        .one(&state.db)
+        .await
+        .unwrap_or(None);
+
+    if let Some((_, Some(n))) = redirect {
+        if let Some(current) = n.slug {
+            return Redirect::permanent(&format!("/api/public/notes/by-slug/{}", current)).into_response();
+        }
+    }
+
+    Json(ApiResponse::<Option<NoteDto>>::success(None)).into_response()
+}
+
+pub async fn highlight_css() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/css; charset=utf-8")], markdown::highlight_css())
 }
\ No newline at end of file
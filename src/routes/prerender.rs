@@ -0,0 +1,158 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter};
+use std::sync::Arc;
+use crate::entity::{category, note};
+use crate::markdown::{self, escape_html};
+use crate::routes::{AppState, notes, tags, web_info};
+
+const DESCRIPTION_CHARS: usize = 160;
+
+// Substrings of user agents that fetch pages without running JavaScript:
+// search engines and the link-preview bots of chat and social apps
+const CRAWLER_AGENTS: &[&str] = &[
+    "googlebot", "bingbot", "baiduspider", "yandex", "duckduckbot", "sogou", "360spider",
+    "bytespider", "petalbot", "applebot", "facebookexternalhit", "twitterbot", "linkedinbot",
+    "slackbot", "discordbot", "telegrambot", "whatsapp", "micromessenger", "skypeuripreview", "embedly",
+];
+
+pub fn is_crawler(user_agent: &str) -> bool {
+    let ua = user_agent.to_lowercase();
+    CRAWLER_AGENTS.iter().any(|bot| ua.contains(bot))
+}
+
+/// Turns a site-relative path such as an uploaded cover into an absolute URL.
+fn absolute(base: &str, url: &str) -> String {
+    if url.starts_with('/') { format!("{}{}", base, url) } else { url.to_string() }
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, Html("<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>404</title></head><body><h1>404</h1></body></html>")).into_response()
+}
+
+// GET /prerender/article/:key, where key is a note id or its slug
+pub async fn note_snapshot(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    // Canonical and og:url come from blogDomain only: a Host header fallback would let
+    // any client poison the copy that nginx or a CDN caches for everyone
+    let site = web_info::load_site_meta(&state.db).await;
+    if site.base_url.is_empty() {
+        return (StatusCode::NOT_FOUND, "Prerendering is off until blogDomain is set").into_response();
+    }
+    let base = site.base_url.clone();
+
+    let lookup = match key.parse::<i32>() {
+        Ok(id) => note::Column::Id.eq(id),
+        Err(_) => note::Column::Slug.eq(key),
+    };
+    let found = note::Entity::find()
+        .filter(notes::public_condition().add(lookup))
+        .find_also_related(category::Entity)
+        .one(&state.db)
+        .await
+        .unwrap_or(None);
+    let Some((n, cat)) = found else {
+        return not_found();
+    };
+
+    let tag_list = tags::load_note_tags(&state.db, &[n.id]).await
+        .unwrap_or_default()
+        .remove(&n.id)
+        .unwrap_or_default();
+    let rendered = state.render_cache.get_or_render(n.id, n.updated_at, &n.content);
+
+    let canonical = format!("{}/article/{}", base, n.id);
+    let description = n.description.clone()
+        .filter(|d| !d.trim().is_empty())
+        .unwrap_or_else(|| markdown::excerpt(&n.content, DESCRIPTION_CHARS));
+    let image = n.cover.as_deref()
+        .filter(|c| !c.is_empty())
+        .or(Some(site.avatar.as_str()).filter(|a| !a.is_empty()))
+        .map(|c| absolute(&base, c));
+    let page_title = if site.title.is_empty() { n.title.clone() } else { format!("{} - {}", n.title, site.title) };
+    let published = n.created_at.and_utc().to_rfc3339();
+    let modified = n.updated_at.and_utc().to_rfc3339();
+
+    let mut keywords: Vec<&str> = cat.iter().map(|c| c.name.as_str()).collect();
+    keywords.extend(tag_list.iter().map(|t| t.title.as_str()));
+
+    let mut ld = serde_json::json!({
+        "@context": "https://schema.org",
+        "@type": "BlogPosting",
+        "headline": n.title,
+        "description": description,
+        "url": canonical,
+        "mainEntityOfPage": { "@type": "WebPage", "@id": canonical },
+        "datePublished": published,
+        "dateModified": modified,
+        "author": { "@type": "Person", "name": site.author },
+        "keywords": keywords.join(","),
+    });
+    if let Some(ref img) = image {
+        ld["image"] = serde_json::Value::String(img.clone());
+    }
+    // "</script>" inside a string must not end the script element
+    let ld = ld.to_string().replace("</", "<\\/");
+
+    let mut head = String::new();
+    head.push_str(&format!("<title>{}</title>\n", escape_html(&page_title)));
+    head.push_str(&format!("<meta name=\"description\" content=\"{}\">\n", escape_html(&description)));
+    if !keywords.is_empty() {
+        head.push_str(&format!("<meta name=\"keywords\" content=\"{}\">\n", escape_html(&keywords.join(","))));
+    }
+    head.push_str(&format!("<link rel=\"canonical\" href=\"{}\">\n", escape_html(&canonical)));
+    head.push_str("<meta property=\"og:type\" content=\"article\">\n");
+    head.push_str(&format!("<meta property=\"og:title\" content=\"{}\">\n", escape_html(&n.title)));
+    head.push_str(&format!("<meta property=\"og:description\" content=\"{}\">\n", escape_html(&description)));
+    head.push_str(&format!("<meta property=\"og:url\" content=\"{}\">\n", escape_html(&canonical)));
+    if !site.title.is_empty() {
+        head.push_str(&format!("<meta property=\"og:site_name\" content=\"{}\">\n", escape_html(&site.title)));
+    }
+    head.push_str(&format!("<meta property=\"article:published_time\" content=\"{}\">\n", published));
+    head.push_str(&format!("<meta property=\"article:modified_time\" content=\"{}\">\n", modified));
+    for t in &tag_list {
+        head.push_str(&format!("<meta property=\"article:tag\" content=\"{}\">\n", escape_html(&t.title)));
+    }
+    match image {
+        Some(ref img) => {
+            head.push_str(&format!("<meta property=\"og:image\" content=\"{}\">\n", escape_html(img)));
+            head.push_str("<meta name=\"twitter:card\" content=\"summary_large_image\">\n");
+            head.push_str(&format!("<meta name=\"twitter:image\" content=\"{}\">\n", escape_html(img)));
+        }
+        None => head.push_str("<meta name=\"twitter:card\" content=\"summary\">\n"),
+    }
+    head.push_str(&format!("<meta name=\"twitter:title\" content=\"{}\">\n", escape_html(&n.title)));
+    head.push_str(&format!("<meta name=\"twitter:description\" content=\"{}\">\n", escape_html(&description)));
+    head.push_str(&format!("<script type=\"application/ld+json\">{}</script>\n", ld));
+
+    // People who open a shared /prerender link are sent on to the SPA page
+    let is_bot = headers.get(header::USER_AGENT)
+        .and_then(|h| h.to_str().ok())
+        .map(is_crawler)
+        .unwrap_or(true);
+    if !is_bot {
+        head.push_str(&format!("<meta http-equiv=\"refresh\" content=\"0; url={}\">\n", escape_html(&canonical)));
+    }
+
+    let page = format!(
+        "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n{head}</head>\n<body>\n<article>\n<h1>{title}</h1>\n<p><time datetime=\"{published}\">{date}</time>{category}</p>\n{content}</article>\n<p><a href=\"{canonical}\">{canonical}</a></p>\n</body>\n</html>\n",
+        head = head,
+        title = escape_html(&n.title),
+        published = published,
+        date = crate::utils::format_datetime(n.created_at),
+        category = cat.map(|c| format!(" · {}", escape_html(&c.name))).unwrap_or_default(),
+        content = rendered.html,
+        canonical = escape_html(&canonical),
+    );
+
+    (
+        [(header::CACHE_CONTROL, "public, max-age=300"), (header::VARY, "User-Agent")],
+        Html(page),
+    ).into_response()
+}
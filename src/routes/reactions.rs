@@ -0,0 +1,258 @@
+use axum::{
+    Json,
+    extract::{ConnectInfo, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, QuerySelect, ActiveModelTrait, Set, ConnectionTrait, DbErr, Condition};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use crate::entity::{note, reaction, talk};
+use crate::routes::{AppState, notes};
+use crate::utils::ApiResponse;
+use crate::views;
+
+pub const TARGET_NOTE: &str = "note";
+pub const TARGET_TALK: &str = "talk";
+pub const EMOJIS: &[&str] = &["👍", "❤️", "😄", "🎉", "😮", "😢"];
+
+// Cached public lists that show reaction counts, by target type
+const NOTE_LIST_PATHS: &[&str] = &["/api/public/notes", "/api/public/notes/page", "/api/public/topnotes"];
+const TALK_LIST_PATHS: &[&str] = &["/api/talk", "/api/public/talk"];
+
+const VISITOR_COOKIE: &str = "saudade_vid";
+const VISITOR_COOKIE_MAX_AGE: i64 = 365 * 24 * 3600;
+
+/// Emoji -> number of reactions.
+pub type ReactionCounts = BTreeMap<String, i64>;
+
+#[derive(Deserialize)]
+pub struct ReactionTarget {
+    #[serde(rename = "targetType")]
+    pub target_type: String,
+    #[serde(rename = "targetId")]
+    pub target_id: i32,
+}
+
+#[derive(Deserialize)]
+pub struct ReactRequest {
+    #[serde(rename = "targetType")]
+    pub target_type: String,
+    #[serde(rename = "targetId")]
+    pub target_id: i32,
+    pub emoji: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetRequest {
+    #[serde(rename = "targetType")]
+    pub target_type: String,
+    #[serde(rename = "targetIds")]
+    pub target_ids: Vec<i32>,
+    // Only this emoji; all of them when absent
+    pub emoji: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+pub struct ReactionSummaryDto {
+    pub counts: ReactionCounts,
+    // Emojis the current visitor has used on this target
+    pub mine: Vec<String>,
+}
+
+/// Reaction counts of many targets at once, for list endpoints.
+pub async fn load_counts<C: ConnectionTrait>(db: &C, target_type: &str, ids: &[i32]) -> Result<HashMap<i32, ReactionCounts>, DbErr> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let rows: Vec<(i32, String, i64)> = reaction::Entity::find()
+        .select_only()
+        .columns([reaction::Column::TargetId, reaction::Column::Emoji])
+        .column_as(reaction::Column::Id.count(), "count")
+        .filter(reaction::Column::TargetType.eq(target_type))
+        .filter(reaction::Column::TargetId.is_in(ids.to_vec()))
+        .group_by(reaction::Column::TargetId)
+        .group_by(reaction::Column::Emoji)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let mut counts: HashMap<i32, ReactionCounts> = HashMap::new();
+    for (id, emoji, count) in rows {
+        counts.entry(id).or_default().insert(emoji, count);
+    }
+    Ok(counts)
+}
+
+/// Removes the reactions of deleted notes or talks.
+pub async fn delete_for<C: ConnectionTrait>(db: &C, target_type: &str, ids: &[i32]) -> Result<(), DbErr> {
+    reaction::Entity::delete_many()
+        .filter(reaction::Column::TargetType.eq(target_type))
+        .filter(reaction::Column::TargetId.is_in(ids.to_vec()))
+        .exec(db)
+        .await
+        .map(|_| ())
+}
+
+fn visitor_cookie(headers: &HeaderMap) -> Option<String> {
+    headers.get_all(header::COOKIE)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == VISITOR_COOKIE)
+        .map(|(_, value)| value.to_string())
+        .filter(|v| !v.is_empty() && v.len() <= 64)
+}
+
+fn visitor_hash(visitor: &str) -> String {
+    hex::encode(Sha256::digest(visitor.as_bytes()))
+}
+
+/// Whether a note (public ones only) or talk can receive reactions and comments.
+pub(crate) async fn target_exists<C: ConnectionTrait>(db: &C, target_type: &str, id: i32) -> Result<bool, DbErr> {
+    match target_type {
+        TARGET_NOTE => Ok(note::Entity::find()
+            .filter(notes::public_condition().add(note::Column::Id.eq(id)))
+            .one(db)
+            .await?
+            .is_some()),
+        TARGET_TALK => Ok(talk::Entity::find_by_id(id).one(db).await?.is_some()),
+        _ => Ok(false),
+    }
+}
+
+async fn summary<C: ConnectionTrait>(db: &C, target_type: &str, id: i32, visitor: Option<&str>) -> Result<ReactionSummaryDto, DbErr> {
+    let counts = load_counts(db, target_type, &[id]).await?.remove(&id).unwrap_or_default();
+    let mine = match visitor {
+        Some(v) => reaction::Entity::find()
+            .select_only()
+            .column(reaction::Column::Emoji)
+            .filter(reaction::Column::TargetType.eq(target_type))
+            .filter(reaction::Column::TargetId.eq(id))
+            .filter(reaction::Column::VisitorHash.eq(visitor_hash(v)))
+            .into_tuple()
+            .all(db)
+            .await?,
+        None => vec![],
+    };
+    Ok(ReactionSummaryDto { counts, mine })
+}
+
+// GET /api/public/reactions?targetType=note&targetId=1
+pub async fn get_reactions(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<ReactionTarget>,
+    headers: HeaderMap,
+) -> Json<ApiResponse<ReactionSummaryDto>> {
+    let visitor = visitor_cookie(&headers);
+    match summary(&state.db, &target.target_type, target.target_id, visitor.as_deref()).await {
+        Ok(s) => Json(ApiResponse::success(s)),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+// POST /api/public/reactions, toggles the visitor's reaction
+pub async fn toggle_reaction(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(payload): Json<ReactRequest>,
+) -> Response {
+    let ip = views::client_ip(&headers, peer.map(|p| p.0));
+    if !state.reaction_limiter.check(&ip) {
+        return Json(ApiResponse::<ReactionSummaryDto> {
+            code: 429,
+            message: "Too many reactions, please slow down".to_string(),
+            data: ReactionSummaryDto::default(),
+        }).into_response();
+    }
+    if !EMOJIS.contains(&payload.emoji.as_str()) {
+        return Json(ApiResponse::<ReactionSummaryDto>::error("Unsupported emoji")).into_response();
+    }
+    match target_exists(&state.db, &payload.target_type, payload.target_id).await {
+        Ok(true) => {}
+        Ok(false) => return Json(ApiResponse::<ReactionSummaryDto>::error("Not found")).into_response(),
+        Err(e) => return Json(ApiResponse::<ReactionSummaryDto>::error(&format!("Error: {}", e))).into_response(),
+    }
+
+    // Without a cookie the visitor is today's IP + UA fingerprint, so dropping the
+    // cookie does not mint a new identity on every request. The cookie handed out is
+    // a random id rather than that fingerprint, which would tie the day's view rows
+    // to every later reaction; only hashes of either are stored
+    let (visitor, new_cookie) = match visitor_cookie(&headers) {
+        Some(v) => (v, None),
+        None => {
+            let user_agent = headers.get(header::USER_AGENT).and_then(|h| h.to_str().ok()).unwrap_or("");
+            let v = state.views.fingerprint(&ip, user_agent);
+            let id = uuid::Uuid::new_v4().simple().to_string();
+            let cookie = format!("{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax", VISITOR_COOKIE, id, VISITOR_COOKIE_MAX_AGE);
+            (v, Some(cookie))
+        }
+    };
+    let hash = visitor_hash(&visitor);
+
+    let mine = Condition::all()
+        .add(reaction::Column::TargetType.eq(payload.target_type.as_str()))
+        .add(reaction::Column::TargetId.eq(payload.target_id))
+        .add(reaction::Column::Emoji.eq(payload.emoji.as_str()))
+        .add(reaction::Column::VisitorHash.eq(hash.as_str()));
+
+    let toggled = async {
+        match reaction::Entity::find().filter(mine.clone()).one(&state.db).await? {
+            Some(_) => {
+                reaction::Entity::delete_many().filter(mine).exec(&state.db).await?;
+            }
+            None => {
+                reaction::ActiveModel {
+                    target_type: Set(payload.target_type.clone()),
+                    target_id: Set(payload.target_id),
+                    emoji: Set(payload.emoji.clone()),
+                    visitor_hash: Set(hash.clone()),
+                    created_at: Set(chrono::Utc::now().naive_utc()),
+                    ..Default::default()
+                }.insert(&state.db).await?;
+            }
+        }
+        summary(&state.db, &payload.target_type, payload.target_id, Some(&visitor)).await
+    }.await;
+
+    let body = match toggled {
+        Ok(s) => {
+            // Only the lists showing this kind of target; anyone can react, so the rest stays cached
+            match payload.target_type.as_str() {
+                TARGET_NOTE => state.response_cache.invalidate_paths(NOTE_LIST_PATHS),
+                TARGET_TALK => state.response_cache.invalidate_paths(TALK_LIST_PATHS),
+                _ => {}
+            }
+            Json(ApiResponse::success(s))
+        }
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    };
+    match new_cookie {
+        Some(cookie) => ([(header::SET_COOKIE, cookie)], body).into_response(),
+        None => body.into_response(),
+    }
+}
+
+// DELETE /api/protected/reactions
+pub async fn reset_reactions(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ResetRequest>,
+) -> Json<ApiResponse<String>> {
+    let mut condition = Condition::all()
+        .add(reaction::Column::TargetType.eq(payload.target_type))
+        .add(reaction::Column::TargetId.is_in(payload.target_ids));
+    if let Some(emoji) = payload.emoji {
+        condition = condition.add(reaction::Column::Emoji.eq(emoji));
+    }
+
+    match reaction::Entity::delete_many().filter(condition).exec(&state.db).await {
+        Ok(res) => Json(ApiResponse::success(format!("Reset {} reactions", res.rows_affected))),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
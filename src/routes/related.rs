@@ -0,0 +1,141 @@
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, QueryOrder, QuerySelect, ConnectionTrait, DbErr, Condition, FromQueryResult};
+use sea_orm::sea_query::{Alias, Expr, Query, SimpleExpr};
+use serde::Serialize;
+use crate::entity::{note, note_tag};
+use crate::routes::notes::public_condition;
+use crate::utils::format_datetime;
+
+const RELATED_LIMIT: u64 = 5;
+const CATEGORY_SCORE: i64 = 1;
+const TAG_SCORE: i64 = 2;
+
+// Short form of a note used for navigation links
+#[derive(Serialize)]
+pub struct NoteLinkDto {
+    #[serde(rename = "noteKey")]
+    pub id: i32,
+    #[serde(rename = "noteTitle")]
+    pub title: String,
+    pub slug: Option<String>,
+    pub description: String,
+    pub cover: String,
+    #[serde(rename = "createTime")]
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<i64>,
+}
+
+fn map_link(n: note::Model, score: Option<i64>) -> NoteLinkDto {
+    NoteLinkDto {
+        id: n.id,
+        title: n.title,
+        slug: n.slug,
+        description: n.description.unwrap_or_default(),
+        cover: n.cover.unwrap_or_default(),
+        created_at: format_datetime(n.created_at),
+        score,
+    }
+}
+
+/// Chronologically previous (older) and next (newer) public notes.
+pub async fn neighbours<C: ConnectionTrait>(db: &C, n: &note::Model) -> Result<(Option<NoteLinkDto>, Option<NoteLinkDto>), DbErr> {
+    let prev = note::Entity::find()
+        .filter(public_condition().add(note::Column::CreatedAt.lt(n.created_at)))
+        .order_by_desc(note::Column::CreatedAt)
+        .one(db)
+        .await?;
+    let next = note::Entity::find()
+        .filter(public_condition().add(note::Column::CreatedAt.gt(n.created_at)))
+        .order_by_asc(note::Column::CreatedAt)
+        .one(db)
+        .await?;
+
+    Ok((prev.map(|p| map_link(p, None)), next.map(|p| map_link(p, None))))
+}
+
+#[derive(FromQueryResult)]
+struct RelatedRow {
+    id: i32,
+    title: String,
+    slug: Option<String>,
+    description: Option<String>,
+    cover: Option<String>,
+    created_at: chrono::NaiveDateTime,
+    score: i64,
+}
+
+/// Public notes ranked by what they share with `n`: each common tag counts
+/// `TAG_SCORE`, the same category counts `CATEGORY_SCORE`. Scoring, ranking and
+/// the limit all happen in one query, which only loads the columns a link needs.
+pub async fn related_notes<C: ConnectionTrait>(db: &C, n: &note::Model) -> Result<Vec<NoteLinkDto>, DbErr> {
+    let own_tags = note_tag::Entity::find()
+        .filter(note_tag::Column::NoteId.eq(n.id))
+        .all(db)
+        .await?;
+    if own_tags.is_empty() && n.category_id.is_none() {
+        return Ok(vec![]);
+    }
+
+    let mut same_tag = Condition::any();
+    for t in &own_tags {
+        same_tag = same_tag.add(
+            Condition::all()
+                .add(note_tag::Column::Level.eq(t.level))
+                .add(note_tag::Column::TagId.eq(t.tag_id)),
+        );
+    }
+
+    let mut score = Expr::value(0);
+    let mut candidate = Condition::any();
+    if !own_tags.is_empty() {
+        let shared = Query::select()
+            .expr(Expr::col((note_tag::Entity, note_tag::Column::Id)).count())
+            .from(note_tag::Entity)
+            .and_where(Expr::col((note_tag::Entity, note_tag::Column::NoteId)).equals((note::Entity, note::Column::Id)))
+            .cond_where(same_tag.clone())
+            .to_owned();
+        score = score.add(Expr::expr(SimpleExpr::SubQuery(None, Box::new(shared.into_sub_query_statement()))).mul(TAG_SCORE));
+
+        let tagged = Query::select()
+            .column(note_tag::Column::NoteId)
+            .from(note_tag::Entity)
+            .cond_where(same_tag)
+            .to_owned();
+        candidate = candidate.add(note::Column::Id.in_subquery(tagged));
+    }
+    if let Some(cat_id) = n.category_id {
+        score = score.add(Expr::case(note::Column::CategoryId.eq(cat_id), CATEGORY_SCORE).finally(0));
+        candidate = candidate.add(note::Column::CategoryId.eq(cat_id));
+    }
+
+    let rows = note::Entity::find()
+        .select_only()
+        .columns([
+            note::Column::Id,
+            note::Column::Title,
+            note::Column::Slug,
+            note::Column::Description,
+            note::Column::Cover,
+            note::Column::CreatedAt,
+        ])
+        .column_as(score, "score")
+        .filter(public_condition().add(note::Column::Id.ne(n.id)).add(candidate))
+        .order_by_desc(Expr::col(Alias::new("score")))
+        .order_by_desc(note::Column::CreatedAt)
+        .limit(RELATED_LIMIT)
+        .into_model::<RelatedRow>()
+        .all(db)
+        .await?;
+
+    Ok(rows.into_iter()
+        .map(|r| NoteLinkDto {
+            id: r.id,
+            title: r.title,
+            slug: r.slug,
+            description: r.description.unwrap_or_default(),
+            cover: r.cover.unwrap_or_default(),
+            created_at: format_datetime(r.created_at),
+            score: Some(r.score),
+        })
+        .collect())
+}
@@ -0,0 +1,239 @@
+use axum::{Json, extract::{State, Path, Query}};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, QueryOrder, QuerySelect, ActiveModelTrait, Set, ConnectionTrait, DbErr, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::sync::Arc;
+use crate::entity::{note, note_revision};
+use crate::events::DomainEvent;
+use crate::routes::{AppState, notes, tags};
+use crate::utils::{ApiResponse, format_datetime};
+
+#[derive(Serialize)]
+pub struct RevisionDto {
+    #[serde(rename = "revisionKey")]
+    pub id: i32,
+    #[serde(rename = "noteKey")]
+    pub note_id: i32,
+    #[serde(rename = "noteTitle")]
+    pub title: String,
+    #[serde(rename = "noteContent", skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    pub description: String,
+    pub cover: String,
+    #[serde(rename = "noteCategory")]
+    pub category_id: Option<i32>,
+    #[serde(rename = "noteTags")]
+    pub tags: String,
+    #[serde(rename = "isTop")]
+    pub is_top: i32,
+    pub status: String,
+    // When the captured version was last saved, and when it was replaced
+    #[serde(rename = "updateTime")]
+    pub note_updated_at: String,
+    #[serde(rename = "createTime")]
+    pub created_at: String,
+}
+
+fn map_revision(r: note_revision::Model, with_content: bool) -> RevisionDto {
+    RevisionDto {
+        id: r.id,
+        note_id: r.note_id,
+        title: r.title,
+        content: if with_content { Some(r.content) } else { None },
+        description: r.description.unwrap_or_default(),
+        cover: r.cover.unwrap_or_default(),
+        category_id: r.category_id,
+        tags: r.tags.unwrap_or_default(),
+        is_top: r.is_top.unwrap_or(0),
+        status: r.status.unwrap_or("published".to_string()),
+        note_updated_at: format_datetime(r.note_updated_at),
+        created_at: format_datetime(r.created_at),
+    }
+}
+
+/// Stores the current state of a note as a revision. Call before overwriting it.
+pub async fn snapshot<C: ConnectionTrait>(db: &C, n: &note::Model) -> Result<(), DbErr> {
+    // Tags are taken from note_tag with their levels; the legacy column has bare ids,
+    // which cannot be restored once an id exists in both tag tables
+    let tags = tags::note_tag_refs(db, n.id).await?;
+    let rev = note_revision::ActiveModel {
+        note_id: Set(n.id),
+        title: Set(n.title.clone()),
+        content: Set(n.content.clone()),
+        description: Set(n.description.clone()),
+        cover: Set(n.cover.clone()),
+        is_top: Set(n.is_top),
+        status: Set(n.status.clone()),
+        tags: Set(Some(tags)),
+        category_id: Set(n.category_id),
+        note_updated_at: Set(n.updated_at),
+        created_at: Set(chrono::Utc::now().naive_utc()),
+        ..Default::default()
+    };
+    rev.insert(db).await.map(|_| ())
+}
+
+pub async fn list_revisions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Json<ApiResponse<Vec<RevisionDto>>> {
+    // Content is blanked out of the listing; fetch a single revision to see it
+    let revisions = note_revision::Entity::find()
+        .select_only()
+        .columns([
+            note_revision::Column::Id,
+            note_revision::Column::NoteId,
+            note_revision::Column::Title,
+            note_revision::Column::Description,
+            note_revision::Column::Cover,
+            note_revision::Column::IsTop,
+            note_revision::Column::Status,
+            note_revision::Column::Tags,
+            note_revision::Column::CategoryId,
+            note_revision::Column::NoteUpdatedAt,
+            note_revision::Column::CreatedAt,
+        ])
+        .column_as(sea_orm::sea_query::Expr::val(""), "content")
+        .filter(note_revision::Column::NoteId.eq(id))
+        .order_by_desc(note_revision::Column::Id)
+        .all(&state.db)
+        .await
+        .unwrap_or(vec![]);
+
+    let dtos = revisions.into_iter().map(|r| map_revision(r, false)).collect();
+    Json(ApiResponse::success(dtos))
+}
+
+pub async fn get_revision(
+    State(state): State<Arc<AppState>>,
+    Path((id, rev_id)): Path<(i32, i32)>,
+) -> Json<ApiResponse<Option<RevisionDto>>> {
+    let rev = note_revision::Entity::find_by_id(rev_id)
+        .filter(note_revision::Column::NoteId.eq(id))
+        .one(&state.db)
+        .await
+        .unwrap_or(None);
+
+    Json(ApiResponse::success(rev.map(|r| map_revision(r, true))))
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    pub from: i32,
+    // Omitted means the note as it is now
+    pub to: Option<i32>,
+}
+
+#[derive(Serialize, Default)]
+pub struct DiffLine {
+    pub tag: String, // "equal" | "insert" | "delete"
+    #[serde(rename = "oldLine")]
+    pub old_line: Option<usize>,
+    #[serde(rename = "newLine")]
+    pub new_line: Option<usize>,
+    pub content: String,
+}
+
+#[derive(Serialize, Default)]
+pub struct DiffDto {
+    #[serde(rename = "oldTitle")]
+    pub old_title: String,
+    #[serde(rename = "newTitle")]
+    pub new_title: String,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Line diff between two texts, numbered from 1 on each side.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            tag: match change.tag() {
+                ChangeTag::Equal => "equal",
+                ChangeTag::Insert => "insert",
+                ChangeTag::Delete => "delete",
+            }.to_string(),
+            old_line: change.old_index().map(|i| i + 1),
+            new_line: change.new_index().map(|i| i + 1),
+            content: change.value().trim_end_matches(['\n', '\r']).to_string(),
+        })
+        .collect()
+}
+
+pub async fn diff_revisions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Query(query): Query<DiffQuery>,
+) -> Json<ApiResponse<DiffDto>> {
+    let load = |rev_id: i32| {
+        note_revision::Entity::find_by_id(rev_id)
+            .filter(note_revision::Column::NoteId.eq(id))
+            .one(&state.db)
+    };
+
+    let old = match load(query.from).await.unwrap_or(None) {
+        Some(r) => (r.title, r.content),
+        None => return Json(ApiResponse::error("Revision not found")),
+    };
+
+    let new = match query.to {
+        Some(to) => load(to).await.unwrap_or(None).map(|r| (r.title, r.content)),
+        None => note::Entity::find_by_id(id).one(&state.db).await.unwrap_or(None).map(|n| (n.title, n.content)),
+    };
+    let Some(new) = new else {
+        return Json(ApiResponse::error("Revision not found"));
+    };
+
+    Json(ApiResponse::success(DiffDto {
+        lines: diff_lines(&old.1, &new.1),
+        old_title: old.0,
+        new_title: new.0,
+    }))
+}
+
+pub async fn restore_revision(
+    State(state): State<Arc<AppState>>,
+    Path((id, rev_id)): Path<(i32, i32)>,
+) -> Json<ApiResponse<String>> {
+    let rev = note_revision::Entity::find_by_id(rev_id)
+        .filter(note_revision::Column::NoteId.eq(id))
+        .one(&state.db)
+        .await
+        .unwrap_or(None);
+    let current = note::Entity::find_by_id(id).one(&state.db).await.unwrap_or(None);
+
+    let (Some(rev), Some(current)) = (rev, current) else {
+        return Json(ApiResponse::error("Revision not found"));
+    };
+
+    // Status and visibility are left as they are; restoring must not publish a draft
+    let previous = current.clone();
+    let was_visible = notes::is_visible(&current);
+    let mut active: note::ActiveModel = current.into();
+    active.title = Set(rev.title);
+    active.content = Set(rev.content);
+    active.description = Set(rev.description);
+    active.cover = Set(rev.cover);
+    active.is_top = Set(rev.is_top);
+    active.category_id = Set(rev.category_id);
+    active.updated_at = Set(chrono::Utc::now().naive_utc());
+    let refs = tags::parse_tag_refs(rev.tags.as_deref().unwrap_or(""));
+
+    // Restoring is itself an update, so the version being replaced stays recoverable
+    let restored = async {
+        let txn = state.db.begin().await?;
+        snapshot(&txn, &previous).await?;
+        active.tags = Set(Some(tags::set_note_tags(&txn, id, &refs).await?));
+        let updated = active.update(&txn).await?;
+        txn.commit().await?;
+        Ok::<_, DbErr>(updated)
+    }.await;
+
+    match restored {
+        Ok(updated) => {
+            state.events.publish(DomainEvent::NoteUpdated { note: updated, was_visible });
+            Json(ApiResponse::success("Revision restored".to_string()))
+        }
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
@@ -0,0 +1,283 @@
+use axum::{Json, extract::{State, Path}};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, QueryOrder, ActiveModelTrait, Set, ConnectionTrait, DbErr, Condition};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::entity::{note, series, series_note};
+use crate::routes::{AppState, notes};
+use crate::utils::{ApiResponse, format_datetime};
+
+#[derive(Serialize, Clone)]
+pub struct SeriesPartDto {
+    #[serde(rename = "noteKey")]
+    pub id: i32,
+    #[serde(rename = "noteTitle")]
+    pub title: String,
+    pub slug: Option<String>,
+    pub description: String,
+    pub cover: String,
+    #[serde(rename = "createTime")]
+    pub created_at: String,
+    pub part: usize, // 1-based
+}
+
+#[derive(Serialize)]
+pub struct SeriesDto {
+    #[serde(rename = "seriesKey")]
+    pub id: i32,
+    #[serde(rename = "seriesTitle")]
+    pub title: String,
+    pub description: String,
+    pub cover: String,
+    #[serde(rename = "createTime")]
+    pub created_at: String,
+    #[serde(rename = "updateTime")]
+    pub updated_at: String,
+    pub parts: Vec<SeriesPartDto>,
+}
+
+// Where a note sits inside its series, returned with the note detail
+#[derive(Serialize)]
+pub struct SeriesContextDto {
+    #[serde(rename = "seriesKey")]
+    pub id: i32,
+    #[serde(rename = "seriesTitle")]
+    pub title: String,
+    pub part: usize,
+    pub total: usize,
+    pub prev: Option<SeriesPartDto>,
+    pub next: Option<SeriesPartDto>,
+}
+
+#[derive(Deserialize)]
+pub struct UpsertSeriesRequest {
+    #[serde(rename = "seriesTitle")]
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub cover: Option<String>,
+    // Ordered note ids; replaces the membership when present
+    #[serde(rename = "noteKeys")]
+    pub note_ids: Option<Vec<i32>>,
+}
+
+/// Public parts of a series in order. `include` keeps that note even when it is not
+/// public, so an admin previewing a draft part still gets its context.
+async fn load_parts<C: ConnectionTrait>(db: &C, series_id: i32, include: Option<i32>) -> Result<Vec<SeriesPartDto>, DbErr> {
+    let mut visible = Condition::any().add(notes::public_condition());
+    if let Some(id) = include {
+        visible = visible.add(note::Column::Id.eq(id));
+    }
+
+    let rows = series_note::Entity::find()
+        .filter(series_note::Column::SeriesId.eq(series_id))
+        .order_by_asc(series_note::Column::Position)
+        .find_also_related(note::Entity)
+        .filter(visible)
+        .all(db)
+        .await?;
+    Ok(rows.into_iter()
+        .filter_map(|(_, n)| n)
+        .enumerate()
+        .map(|(i, n)| SeriesPartDto {
+            id: n.id,
+            title: n.title,
+            slug: n.slug,
+            description: n.description.unwrap_or_default(),
+            cover: n.cover.unwrap_or_default(),
+            created_at: format_datetime(n.created_at),
+            part: i + 1,
+        })
+        .collect())
+}
+
+fn map_series(s: series::Model, parts: Vec<SeriesPartDto>) -> SeriesDto {
+    SeriesDto {
+        id: s.id,
+        title: s.title,
+        description: s.description.unwrap_or_default(),
+        cover: s.cover.unwrap_or_default(),
+        created_at: format_datetime(s.created_at),
+        updated_at: format_datetime(s.updated_at),
+        parts,
+    }
+}
+
+/// Series context for a note on its detail page, if it belongs to one.
+pub async fn context_for_note<C: ConnectionTrait>(db: &C, note_id: i32) -> Result<Option<SeriesContextDto>, DbErr> {
+    let membership = series_note::Entity::find()
+        .filter(series_note::Column::NoteId.eq(note_id))
+        .find_also_related(series::Entity)
+        .one(db)
+        .await?;
+    let Some((_, Some(s))) = membership else {
+        return Ok(None);
+    };
+
+    let parts = load_parts(db, s.id, Some(note_id)).await?;
+    let Some(idx) = parts.iter().position(|p| p.id == note_id) else {
+        return Ok(None);
+    };
+
+    Ok(Some(SeriesContextDto {
+        id: s.id,
+        title: s.title,
+        part: idx + 1,
+        total: parts.len(),
+        prev: idx.checked_sub(1).and_then(|i| parts.get(i).cloned()),
+        next: parts.get(idx + 1).cloned(),
+    }))
+}
+
+async fn set_members<C: ConnectionTrait>(db: &C, series_id: i32, note_ids: &[i32]) -> Result<(), DbErr> {
+    // A note can only be in one series, so it is taken out of any other one first
+    series_note::Entity::delete_many()
+        .filter(
+            Condition::any()
+                .add(series_note::Column::SeriesId.eq(series_id))
+                .add(series_note::Column::NoteId.is_in(note_ids.to_vec()))
+        )
+        .exec(db)
+        .await?;
+
+    let mut seen = Vec::new();
+    for id in note_ids {
+        if !seen.contains(id) {
+            seen.push(*id);
+        }
+    }
+    if seen.is_empty() {
+        return Ok(());
+    }
+
+    let rows = seen.iter().enumerate().map(|(pos, id)| series_note::ActiveModel {
+        series_id: Set(series_id),
+        note_id: Set(*id),
+        position: Set(pos as i32),
+        ..Default::default()
+    });
+    series_note::Entity::insert_many(rows).exec(db).await.map(|_| ())
+}
+
+pub async fn get_public_series(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Json<ApiResponse<Option<SeriesDto>>> {
+    let s = series::Entity::find_by_id(id).one(&state.db).await.unwrap_or(None);
+    let Some(s) = s else {
+        return Json(ApiResponse::success(None));
+    };
+
+    let parts = load_parts(&state.db, s.id, None).await.unwrap_or(vec![]);
+    Json(ApiResponse::success(Some(map_series(s, parts))))
+}
+
+pub async fn list_series(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<Vec<SeriesDto>>> {
+    let all = series::Entity::find()
+        .order_by_desc(series::Column::UpdatedAt)
+        .all(&state.db)
+        .await
+        .unwrap_or(vec![]);
+
+    let ids: Vec<i32> = all.iter().map(|s| s.id).collect();
+    let rows = series_note::Entity::find()
+        .filter(series_note::Column::SeriesId.is_in(ids))
+        .order_by_asc(series_note::Column::Position)
+        .find_also_related(note::Entity)
+        .all(&state.db)
+        .await
+        .unwrap_or(vec![]);
+
+    let mut parts: HashMap<i32, Vec<SeriesPartDto>> = HashMap::new();
+    for (member, n) in rows {
+        let Some(n) = n else { continue };
+        let list = parts.entry(member.series_id).or_default();
+        list.push(SeriesPartDto {
+            id: n.id,
+            title: n.title,
+            slug: n.slug,
+            description: n.description.unwrap_or_default(),
+            cover: n.cover.unwrap_or_default(),
+            created_at: format_datetime(n.created_at),
+            part: list.len() + 1,
+        });
+    }
+
+    let dtos = all.into_iter().map(|s| {
+        let p = parts.remove(&s.id).unwrap_or_default();
+        map_series(s, p)
+    }).collect();
+
+    Json(ApiResponse::success(dtos))
+}
+
+pub async fn create_series(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<UpsertSeriesRequest>,
+) -> Json<ApiResponse<String>> {
+    let now = chrono::Utc::now().naive_utc();
+    let new_series = series::ActiveModel {
+        title: Set(payload.title.unwrap_or_else(|| "New Series".to_string())),
+        description: Set(payload.description),
+        cover: Set(payload.cover),
+        created_at: Set(now),
+        updated_at: Set(now),
+        ..Default::default()
+    };
+
+    let created = match new_series.insert(&state.db).await {
+        Ok(s) => s,
+        Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+    };
+
+    if let Some(ids) = payload.note_ids {
+        if let Err(e) = set_members(&state.db, created.id, &ids).await {
+            return Json(ApiResponse::error(&format!("Error: {}", e)));
+        }
+    }
+
+    Json(ApiResponse::success("Series created".to_string()))
+}
+
+pub async fn update_series(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(payload): Json<UpsertSeriesRequest>,
+) -> Json<ApiResponse<String>> {
+    let s = series::Entity::find_by_id(id).one(&state.db).await.unwrap_or(None);
+    let Some(s) = s else {
+        return Json(ApiResponse::error("Not found"));
+    };
+
+    let mut active: series::ActiveModel = s.into();
+    if let Some(v) = payload.title.filter(|v| !v.is_empty()) { active.title = Set(v); }
+    if let Some(v) = payload.description { active.description = Set(Some(v)); }
+    if let Some(v) = payload.cover { active.cover = Set(Some(v)); }
+    active.updated_at = Set(chrono::Utc::now().naive_utc());
+
+    if let Err(e) = active.update(&state.db).await {
+        return Json(ApiResponse::error(&format!("Error: {}", e)));
+    }
+
+    if let Some(ids) = payload.note_ids {
+        if let Err(e) = set_members(&state.db, id, &ids).await {
+            return Json(ApiResponse::error(&format!("Error: {}", e)));
+        }
+    }
+
+    Json(ApiResponse::success("Updated".to_string()))
+}
+
+pub async fn delete_series(
+    State(state): State<Arc<AppState>>,
+    Json(keys): Json<Vec<i32>>,
+) -> Json<ApiResponse<String>> {
+    match series::Entity::delete_many()
+        .filter(series::Column::Id.is_in(keys))
+        .exec(&state.db)
+        .await {
+        Ok(_) => Json(ApiResponse::success("Deleted".to_string())),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
@@ -0,0 +1,168 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sea_orm::{EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use sea_orm::sea_query::Expr;
+use std::sync::Arc;
+use crate::entity::{note, talk};
+use crate::markdown::escape_html;
+use crate::routes::{AppState, notes, web_info};
+
+// Limit from the sitemaps.org protocol; above it /sitemap.xml becomes an index
+pub const MAX_URLS_PER_SITEMAP: usize = 50_000;
+
+pub struct SitemapUrl {
+    pub path: String,
+    pub lastmod: Option<chrono::NaiveDateTime>,
+}
+
+/// `blogDomain` as an absolute base URL. The request's Host header is never used
+/// instead, since any client can set it.
+async fn site_base(state: &AppState) -> Option<String> {
+    let site = web_info::load_site_meta(&state.db).await;
+    Some(site.base_url).filter(|b| !b.is_empty())
+}
+
+fn no_domain() -> Response {
+    (StatusCode::NOT_FOUND, "Sitemaps are off until blogDomain is set").into_response()
+}
+
+fn w3c_date(dt: chrono::NaiveDateTime) -> String {
+    dt.and_utc().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// Every public page of the SPA worth indexing.
+async fn collect_urls(state: &AppState) -> Vec<SitemapUrl> {
+    // Only ids and timestamps are needed, so note bodies are never loaded
+    let public_notes: Vec<(i32, Option<i32>, chrono::NaiveDateTime)> = note::Entity::find()
+        .select_only()
+        .columns([note::Column::Id, note::Column::CategoryId, note::Column::UpdatedAt])
+        .filter(notes::public_condition())
+        .order_by_desc(note::Column::UpdatedAt)
+        .into_tuple()
+        .all(&state.db)
+        .await
+        .unwrap_or(vec![]);
+
+    let latest_note = public_notes.iter().map(|(_, _, u)| *u).max();
+    let latest_talk: Option<chrono::NaiveDateTime> = talk::Entity::find()
+        .select_only()
+        .column_as(Expr::col(talk::Column::UpdatedAt).max(), "latest")
+        .into_tuple::<Option<chrono::NaiveDateTime>>()
+        .one(&state.db)
+        .await
+        .unwrap_or(None)
+        .flatten();
+
+    let mut urls = vec![
+        SitemapUrl { path: "/".to_string(), lastmod: latest_note },
+        SitemapUrl { path: "/times".to_string(), lastmod: latest_note },
+        SitemapUrl { path: "/talk".to_string(), lastmod: latest_talk },
+        SitemapUrl { path: "/friends".to_string(), lastmod: None },
+        SitemapUrl { path: "/about".to_string(), lastmod: None },
+    ];
+
+    // Categories only get listed when they have public notes; lastmod is their newest note.
+    // Tag filters are a query string on the home page, not a routable URL, so they stay out
+    let mut categories: std::collections::BTreeMap<i32, chrono::NaiveDateTime> = Default::default();
+    for (_, cat, updated) in &public_notes {
+        if let Some(c) = cat {
+            let e = categories.entry(*c).or_insert(*updated);
+            if *updated > *e { *e = *updated; }
+        }
+    }
+    for (id, lastmod) in categories {
+        urls.push(SitemapUrl { path: format!("/category/{}", id), lastmod: Some(lastmod) });
+    }
+
+    for (id, _, updated) in public_notes {
+        urls.push(SitemapUrl { path: format!("/article/{}", id), lastmod: Some(updated) });
+    }
+
+    urls
+}
+
+pub fn render_urlset(base: &str, urls: &[SitemapUrl]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for u in urls {
+        xml.push_str("<url>");
+        xml.push_str(&format!("<loc>{}</loc>", escape_html(&format!("{}{}", base, u.path))));
+        if let Some(lastmod) = u.lastmod {
+            xml.push_str(&format!("<lastmod>{}</lastmod>", w3c_date(lastmod)));
+        }
+        xml.push_str("</url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+pub fn render_index(base: &str, chunks: &[&[SitemapUrl]]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for (i, chunk) in chunks.iter().enumerate() {
+        xml.push_str("<sitemap>");
+        xml.push_str(&format!("<loc>{}</loc>", escape_html(&format!("{}/sitemaps/{}.xml", base, i + 1))));
+        if let Some(lastmod) = chunk.iter().filter_map(|u| u.lastmod).max() {
+            xml.push_str(&format!("<lastmod>{}</lastmod>", w3c_date(lastmod)));
+        }
+        xml.push_str("</sitemap>\n");
+    }
+    xml.push_str("</sitemapindex>\n");
+    xml
+}
+
+fn xml_response(body: String) -> Response {
+    ([(header::CONTENT_TYPE, "application/xml; charset=utf-8")], body).into_response()
+}
+
+// GET /sitemap.xml
+pub async fn sitemap(
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let Some(base) = site_base(&state).await else {
+        return no_domain();
+    };
+    let urls = collect_urls(&state).await;
+
+    if urls.len() <= MAX_URLS_PER_SITEMAP {
+        return xml_response(render_urlset(&base, &urls));
+    }
+    let chunks: Vec<&[SitemapUrl]> = urls.chunks(MAX_URLS_PER_SITEMAP).collect();
+    xml_response(render_index(&base, &chunks))
+}
+
+// GET /sitemaps/:page (e.g. /sitemaps/2.xml), only referenced when the index is in use
+pub async fn sitemap_page(
+    State(state): State<Arc<AppState>>,
+    Path(page): Path<String>,
+) -> Response {
+    let Some(page) = page.strip_suffix(".xml").and_then(|p| p.parse::<usize>().ok()).filter(|p| *p > 0) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let Some(base) = site_base(&state).await else {
+        return no_domain();
+    };
+    let urls = collect_urls(&state).await;
+    match urls.chunks(MAX_URLS_PER_SITEMAP).nth(page - 1) {
+        Some(chunk) => xml_response(render_urlset(&base, chunk)),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+// GET /robots.txt
+pub async fn robots(
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let mut body = "User-agent: *\nAllow: /\nDisallow: /dashboard\nDisallow: /login\nDisallow: /api/protect/\nDisallow: /api/protected/\n".to_string();
+    // The sitemap lives at an absolute URL, so it is only announced once blogDomain is set
+    if let Some(base) = site_base(&state).await {
+        body.push_str(&format!("\nSitemap: {}/sitemap.xml\n", base));
+    }
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response()
+}
@@ -0,0 +1,318 @@
+use axum::{Json, extract::{State, Query}};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, QuerySelect, QueryOrder, PaginatorTrait, ConnectionTrait, DbErr};
+use sea_orm::sea_query::Expr;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::entity::{category, friend, image, note, note_tag, note_view, tag_one, tag_two, talk};
+use crate::routes::AppState;
+use crate::routes::archive::LOCAL_CREATED_AT;
+use crate::text::terms;
+use crate::utils::ApiResponse;
+
+const TOP_TERMS: usize = 50;
+const DEFAULT_VIEW_DAYS: i64 = 30;
+const MAX_VIEW_DAYS: i64 = 366;
+const TOP_VIEWED: u64 = 10;
+
+#[derive(Serialize, Default)]
+pub struct StatsTotals {
+    pub notes: i64,
+    // Keyed by status; notes without a status count as published
+    #[serde(rename = "notesByStatus")]
+    pub notes_by_status: HashMap<String, i64>,
+    pub talks: u64,
+    pub friends: u64,
+    pub images: u64,
+    // Characters of Markdown source, the usual 字数 for a Chinese blog
+    #[serde(rename = "totalWords")]
+    pub total_words: i64,
+}
+
+#[derive(Serialize)]
+pub struct MonthCount {
+    pub month: String, // YYYY-MM, Beijing time
+    pub count: i64,
+}
+
+#[derive(Serialize)]
+pub struct CategoryCount {
+    #[serde(rename = "categoryKey")]
+    pub id: Option<i32>,
+    #[serde(rename = "categoryTitle")]
+    pub title: String,
+    pub count: i64,
+}
+
+#[derive(Serialize)]
+pub struct TagCount {
+    #[serde(rename = "tagKey")]
+    pub id: i32,
+    pub title: String,
+    pub color: String,
+    pub level: i32,
+    pub count: i64,
+}
+
+// Shaped for react-tagcloud: { value, count }
+#[derive(Serialize)]
+pub struct TermCount {
+    pub value: String,
+    pub count: i64,
+}
+
+#[derive(Serialize, Default)]
+pub struct StatsDto {
+    pub totals: StatsTotals,
+    #[serde(rename = "notesPerMonth")]
+    pub notes_per_month: Vec<MonthCount>,
+    pub categories: Vec<CategoryCount>,
+    pub tags: Vec<TagCount>,
+    #[serde(rename = "topTerms")]
+    pub top_terms: Vec<TermCount>,
+}
+
+async fn totals<C: ConnectionTrait>(db: &C) -> Result<StatsTotals, DbErr> {
+    let by_status: Vec<(Option<String>, i64)> = note::Entity::find()
+        .select_only()
+        .column(note::Column::Status)
+        .column_as(note::Column::Id.count(), "count")
+        .group_by(note::Column::Status)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let mut totals = StatsTotals::default();
+    for (status, count) in by_status {
+        let key = status.filter(|s| !s.is_empty()).unwrap_or_else(|| "published".to_string());
+        *totals.notes_by_status.entry(key).or_default() += count;
+        totals.notes += count;
+    }
+
+    // SUM() comes back as DECIMAL on MySQL, hence the cast
+    totals.total_words = note::Entity::find()
+        .select_only()
+        .column_as(Expr::cust("CAST(COALESCE(SUM(CHAR_LENGTH(`note`.`content`)), 0) AS SIGNED)"), "words")
+        .into_tuple::<i64>()
+        .one(db)
+        .await?
+        .unwrap_or(0);
+
+    totals.talks = talk::Entity::find().count(db).await?;
+    totals.friends = friend::Entity::find().count(db).await?;
+    totals.images = image::Entity::find().count(db).await?;
+    Ok(totals)
+}
+
+async fn notes_per_month<C: ConnectionTrait>(db: &C) -> Result<Vec<MonthCount>, DbErr> {
+    let month = format!("DATE_FORMAT({}, '%Y-%m')", LOCAL_CREATED_AT);
+    let rows: Vec<(String, i64)> = note::Entity::find()
+        .select_only()
+        .column_as(Expr::cust(month.clone()), "month")
+        .column_as(note::Column::Id.count(), "count")
+        .group_by(Expr::cust(month.clone()))
+        .order_by_asc(Expr::cust(month))
+        .into_tuple()
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().map(|(month, count)| MonthCount { month, count }).collect())
+}
+
+async fn category_distribution<C: ConnectionTrait>(db: &C) -> Result<Vec<CategoryCount>, DbErr> {
+    let counts: Vec<(Option<i32>, i64)> = note::Entity::find()
+        .select_only()
+        .column(note::Column::CategoryId)
+        .column_as(note::Column::Id.count(), "count")
+        .group_by(note::Column::CategoryId)
+        .into_tuple()
+        .all(db)
+        .await?;
+    let names: HashMap<i32, String> = category::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|c| (c.id, c.name))
+        .collect();
+
+    let mut dist: Vec<CategoryCount> = counts.into_iter().map(|(id, count)| CategoryCount {
+        title: id.and_then(|i| names.get(&i).cloned()).unwrap_or_else(|| "未分类".to_string()),
+        id,
+        count,
+    }).collect();
+    dist.sort_by_key(|d| std::cmp::Reverse(d.count));
+    Ok(dist)
+}
+
+async fn tag_distribution<C: ConnectionTrait>(db: &C) -> Result<Vec<TagCount>, DbErr> {
+    let counts: Vec<(i32, i32, i64)> = note_tag::Entity::find()
+        .select_only()
+        .columns([note_tag::Column::TagId, note_tag::Column::Level])
+        .column_as(note_tag::Column::Id.count(), "count")
+        .group_by(note_tag::Column::TagId)
+        .group_by(note_tag::Column::Level)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let ones: HashMap<i32, tag_one::Model> = tag_one::Entity::find().all(db).await?
+        .into_iter().map(|t| (t.id, t)).collect();
+    let twos: HashMap<i32, tag_two::Model> = tag_two::Entity::find().all(db).await?
+        .into_iter().map(|t| (t.id, t)).collect();
+
+    let mut dist: Vec<TagCount> = counts.into_iter().filter_map(|(id, level, count)| {
+        let (title, color) = if level == 1 {
+            let t = ones.get(&id)?;
+            (t.name.clone(), t.color.clone())
+        } else {
+            let t = twos.get(&id)?;
+            let parent_color = t.tag_one_id.and_then(|p| ones.get(&p)).and_then(|p| p.color.clone());
+            (t.name.clone(), t.color.clone().filter(|c| !c.is_empty()).or(parent_color))
+        };
+        Some(TagCount { id, title, color: color.unwrap_or_default(), level, count })
+    }).collect();
+    dist.sort_by_key(|d| std::cmp::Reverse(d.count));
+    Ok(dist)
+}
+
+/// Terms ranked by how many notes mention them in the title or description.
+async fn top_terms<C: ConnectionTrait>(db: &C) -> Result<Vec<TermCount>, DbErr> {
+    let rows: Vec<(String, Option<String>)> = note::Entity::find()
+        .select_only()
+        .columns([note::Column::Title, note::Column::Description])
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let mut freq: HashMap<String, i64> = HashMap::new();
+    for (title, description) in rows {
+        let text = format!("{} {}", title, description.unwrap_or_default());
+        for term in terms(&text) {
+            *freq.entry(term).or_default() += 1;
+        }
+    }
+
+    let mut ranked: Vec<TermCount> = freq.into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(value, count)| TermCount { value, count })
+        .collect();
+    ranked.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+    ranked.truncate(TOP_TERMS);
+    Ok(ranked)
+}
+
+// GET /api/protected/stats
+pub async fn get_stats(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<StatsDto>> {
+    let db = &state.db;
+    let stats = async {
+        Ok::<_, DbErr>(StatsDto {
+            totals: totals(db).await?,
+            notes_per_month: notes_per_month(db).await?,
+            categories: category_distribution(db).await?,
+            tags: tag_distribution(db).await?,
+            top_terms: top_terms(db).await?,
+        })
+    }.await;
+
+    match stats {
+        Ok(s) => Json(ApiResponse::success(s)),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ViewSummaryQuery {
+    pub days: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct DailyViews {
+    pub date: String,
+    pub pv: i64,
+    pub uv: i64,
+}
+
+#[derive(Serialize)]
+pub struct NoteViews {
+    #[serde(rename = "noteKey")]
+    pub id: i32,
+    #[serde(rename = "noteTitle")]
+    pub title: String,
+    pub pv: i64,
+    pub uv: i64,
+}
+
+#[derive(Serialize, Default)]
+pub struct ViewSummaryDto {
+    pub days: i64,
+    pub pv: i64,
+    // Sum of daily unique visitors; visitor hashes cannot be linked across days
+    pub uv: i64,
+    pub daily: Vec<DailyViews>,
+    #[serde(rename = "topNotes")]
+    pub top_notes: Vec<NoteViews>,
+}
+
+async fn view_summary<C: ConnectionTrait>(db: &C, days: i64) -> Result<ViewSummaryDto, DbErr> {
+    let today = (chrono::Utc::now() + chrono::Duration::hours(8)).date_naive();
+    let since = today - chrono::Duration::days(days - 1);
+
+    let daily: Vec<(chrono::NaiveDate, i64, i64)> = note_view::Entity::find()
+        .select_only()
+        .column(note_view::Column::ViewDate)
+        .column_as(Expr::cust("CAST(SUM(`note_view`.`hits`) AS SIGNED)"), "pv")
+        .column_as(Expr::cust("COUNT(DISTINCT `note_view`.`visitor_hash`)"), "uv")
+        .filter(note_view::Column::ViewDate.gte(since))
+        .group_by(note_view::Column::ViewDate)
+        .order_by_asc(note_view::Column::ViewDate)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let per_note: Vec<(i32, i64, i64)> = note_view::Entity::find()
+        .select_only()
+        .column(note_view::Column::NoteId)
+        .column_as(Expr::cust("CAST(SUM(`note_view`.`hits`) AS SIGNED)"), "pv")
+        .column_as(note_view::Column::Id.count(), "uv")
+        .filter(note_view::Column::ViewDate.gte(since))
+        .group_by(note_view::Column::NoteId)
+        .order_by_desc(Expr::cust("`uv`"))
+        .limit(TOP_VIEWED)
+        .into_tuple()
+        .all(db)
+        .await?;
+    let titles: HashMap<i32, String> = note::Entity::find()
+        .select_only()
+        .columns([note::Column::Id, note::Column::Title])
+        .filter(note::Column::Id.is_in(per_note.iter().map(|(id, _, _)| *id).collect::<Vec<_>>()))
+        .into_tuple::<(i32, String)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+
+    Ok(ViewSummaryDto {
+        days,
+        pv: daily.iter().map(|(_, pv, _)| pv).sum(),
+        uv: daily.iter().map(|(_, _, uv)| uv).sum(),
+        daily: daily.into_iter()
+            .map(|(date, pv, uv)| DailyViews { date: date.format("%Y-%m-%d").to_string(), pv, uv })
+            .collect(),
+        top_notes: per_note.into_iter()
+            .map(|(id, pv, uv)| NoteViews { title: titles.get(&id).cloned().unwrap_or_default(), id, pv, uv })
+            .collect(),
+    })
+}
+
+// GET /api/protected/stats/views?days=30
+pub async fn get_view_summary(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ViewSummaryQuery>,
+) -> Json<ApiResponse<ViewSummaryDto>> {
+    let days = query.days.unwrap_or(DEFAULT_VIEW_DAYS).clamp(1, MAX_VIEW_DAYS);
+    match view_summary(&state.db, days).await {
+        Ok(s) => Json(ApiResponse::success(s)),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
@@ -1,8 +1,10 @@
 use axum::{Json, extract::State};
-use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, Set};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, Set, ConnectionTrait, DbErr};
+use sea_orm::sea_query::{Cond, Query, SimpleExpr};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
-use crate::entity::{tag_one, tag_two};
+use crate::entity::{note, note_tag, tag_one, tag_two};
 use crate::routes::AppState;
 use crate::utils::ApiResponse;
 
@@ -30,6 +32,267 @@ pub struct TagTwoDto {
     pub father_tag: String, 
 }
 
+// Tag as attached to a note, resolved from note_tag
+#[derive(Serialize, Clone, Debug)]
+pub struct NoteTagDto {
+    #[serde(rename = "tagKey")]
+    pub id: i32,
+    pub title: String,
+    pub color: String,
+    pub level: i32,
+}
+
+/// A tag as sent by the client. `level` is 1 for tag_one and 2 for tag_two; the two
+/// tables share an id space, so a bare id (`level: None`) only comes from legacy input.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "TagRefInput")]
+pub struct TagRef {
+    pub id: i32,
+    pub level: Option<i32>,
+}
+
+// `{"id": 7, "level": 2}` or the level-prefixed `"2:7"`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TagRefInput {
+    Pair { id: i32, level: i32 },
+    Prefixed(String),
+}
+
+impl TryFrom<TagRefInput> for TagRef {
+    type Error = String;
+
+    fn try_from(input: TagRefInput) -> Result<Self, Self::Error> {
+        let r = match input {
+            TagRefInput::Pair { id, level } => TagRef { id, level: Some(level) },
+            TagRefInput::Prefixed(s) => parse_tag_ref(&s).ok_or_else(|| format!("invalid tag reference `{}`", s))?,
+        };
+        match r.level {
+            Some(1 | 2) => Ok(r),
+            _ => Err(format!("tag {} needs a level of 1 or 2", r.id)),
+        }
+    }
+}
+
+/// Parses `2:7` or a bare `7`.
+pub fn parse_tag_ref(raw: &str) -> Option<TagRef> {
+    match raw.trim().split_once(':') {
+        Some((level, id)) => Some(TagRef { id: id.trim().parse().ok()?, level: Some(level.trim().parse().ok()?) }),
+        None => Some(TagRef { id: raw.trim().parse().ok()?, level: None }),
+    }
+}
+
+/// Parses the legacy comma separated `noteTags` string.
+pub fn parse_tag_refs(raw: &str) -> Vec<TagRef> {
+    raw.split(',').filter_map(parse_tag_ref).collect()
+}
+
+async fn existing_tag_ids<C: ConnectionTrait>(db: &C, refs: &[TagRef]) -> Result<(Vec<i32>, Vec<i32>), DbErr> {
+    let ids: Vec<i32> = refs.iter().map(|r| r.id).collect();
+    let ones = tag_one::Entity::find()
+        .filter(tag_one::Column::Id.is_in(ids.clone()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+    let twos = tag_two::Entity::find()
+        .filter(tag_two::Column::Id.is_in(ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+    Ok((ones, twos))
+}
+
+// Bare ids that exist in both tag tables are an error rather than a guess
+fn resolve_against(refs: &[TagRef], ones: &[i32], twos: &[i32]) -> Result<Vec<(i32, i32)>, DbErr> {
+    let mut resolved: Vec<(i32, i32)> = Vec::new();
+    let mut ambiguous: Vec<String> = Vec::new();
+    for r in refs {
+        let level = match r.level {
+            Some(1) if ones.contains(&r.id) => 1,
+            Some(2) if twos.contains(&r.id) => 2,
+            None if ones.contains(&r.id) && twos.contains(&r.id) => {
+                ambiguous.push(r.id.to_string());
+                continue;
+            }
+            None if ones.contains(&r.id) => 1,
+            None if twos.contains(&r.id) => 2,
+            _ => continue,
+        };
+        if !resolved.contains(&(level, r.id)) {
+            resolved.push((level, r.id));
+        }
+    }
+    if !ambiguous.is_empty() {
+        return Err(DbErr::Custom(format!(
+            "Tag ids {} exist at both levels, send them as `level:id`",
+            ambiguous.join(",")
+        )));
+    }
+    Ok(resolved)
+}
+
+/// Replaces the tags of a note. Returns the id list to keep in the legacy `note.tags` column.
+/// That column stays bare ids, since older clients `parseInt` each entry; the level of an id
+/// that exists in both tables only lives in `note_tag`.
+pub async fn set_note_tags<C: ConnectionTrait>(db: &C, note_id: i32, refs: &[TagRef]) -> Result<String, DbErr> {
+    let (ones, twos) = existing_tag_ids(db, refs).await?;
+    let resolved = resolve_against(refs, &ones, &twos)?;
+
+    note_tag::Entity::delete_many()
+        .filter(note_tag::Column::NoteId.eq(note_id))
+        .exec(db)
+        .await?;
+
+    if !resolved.is_empty() {
+        let rows = resolved.iter().map(|(level, tag_id)| note_tag::ActiveModel {
+            note_id: Set(note_id),
+            tag_id: Set(*tag_id),
+            level: Set(*level),
+            ..Default::default()
+        });
+        note_tag::Entity::insert_many(rows).exec(db).await?;
+    }
+
+    Ok(resolved.iter().map(|(_, id)| id.to_string()).collect::<Vec<_>>().join(","))
+}
+
+/// The note's tags as level-prefixed refs (`1:3,2:7`), which `parse_tag_refs` reads back
+/// without having to guess the level.
+pub async fn note_tag_refs<C: ConnectionTrait>(db: &C, note_id: i32) -> Result<String, DbErr> {
+    let rows = note_tag::Entity::find()
+        .filter(note_tag::Column::NoteId.eq(note_id))
+        .all(db)
+        .await?;
+    Ok(rows.iter().map(|r| format!("{}:{}", r.level, r.tag_id)).collect::<Vec<_>>().join(","))
+}
+
+/// Tags of each given note, with colors. Second-level tags without a color of their own use their parent's.
+pub async fn load_note_tags<C: ConnectionTrait>(db: &C, note_ids: &[i32]) -> Result<HashMap<i32, Vec<NoteTagDto>>, DbErr> {
+    let mut result: HashMap<i32, Vec<NoteTagDto>> = HashMap::new();
+    if note_ids.is_empty() {
+        return Ok(result);
+    }
+
+    let rows = note_tag::Entity::find()
+        .filter(note_tag::Column::NoteId.is_in(note_ids.to_vec()))
+        .all(db)
+        .await?;
+
+    let one_ids: Vec<i32> = rows.iter().filter(|r| r.level == 1).map(|r| r.tag_id).collect();
+    let two_ids: Vec<i32> = rows.iter().filter(|r| r.level == 2).map(|r| r.tag_id).collect();
+
+    let ones: HashMap<i32, tag_one::Model> = tag_one::Entity::find()
+        .filter(tag_one::Column::Id.is_in(one_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|t| (t.id, t))
+        .collect();
+    let twos: HashMap<i32, (tag_two::Model, Option<tag_one::Model>)> = tag_two::Entity::find()
+        .filter(tag_two::Column::Id.is_in(two_ids))
+        .find_also_related(tag_one::Entity)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(t, parent)| (t.id, (t, parent)))
+        .collect();
+
+    for r in rows {
+        let dto = match r.level {
+            1 => ones.get(&r.tag_id).map(|t| NoteTagDto {
+                id: t.id,
+                title: t.name.clone(),
+                color: t.color.clone().unwrap_or_default(),
+                level: 1,
+            }),
+            _ => twos.get(&r.tag_id).map(|(t, parent)| NoteTagDto {
+                id: t.id,
+                title: t.name.clone(),
+                color: t.color.clone()
+                    .filter(|c| !c.is_empty())
+                    .or_else(|| parent.as_ref().and_then(|p| p.color.clone()))
+                    .unwrap_or_default(),
+                level: 2,
+            }),
+        };
+        if let Some(dto) = dto {
+            result.entry(r.note_id).or_default().push(dto);
+        }
+    }
+    Ok(result)
+}
+
+/// Name of a tag, looked up the way `tag_filter` matches it: without a level a
+/// first-level tag is preferred.
+pub async fn tag_name<C: ConnectionTrait>(db: &C, tag_id: i32, level: Option<i32>) -> Result<Option<String>, DbErr> {
+    if level != Some(2) {
+        if let Some(t) = tag_one::Entity::find_by_id(tag_id).one(db).await? {
+            return Ok(Some(t.name));
+        }
+    }
+    if level != Some(1) {
+        return Ok(tag_two::Entity::find_by_id(tag_id).one(db).await?.map(|t| t.name));
+    }
+    Ok(None)
+}
+
+/// Notes tagged with `tag_id`. A first-level tag also matches notes tagged with any of its children.
+/// `level` pins the id to one tag table; without it both are tried.
+pub fn tag_filter(tag_id: i32, level: Option<i32>) -> SimpleExpr {
+    let mut cond = Cond::any();
+    if level != Some(2) {
+        let children = Query::select()
+            .column(tag_two::Column::Id)
+            .from(tag_two::Entity)
+            .and_where(tag_two::Column::TagOneId.eq(tag_id))
+            .to_owned();
+        cond = cond
+            .add(Cond::all().add(note_tag::Column::Level.eq(1)).add(note_tag::Column::TagId.eq(tag_id)))
+            .add(Cond::all().add(note_tag::Column::Level.eq(2)).add(note_tag::Column::TagId.in_subquery(children)));
+    }
+    if level != Some(1) {
+        cond = cond.add(Cond::all().add(note_tag::Column::Level.eq(2)).add(note_tag::Column::TagId.eq(tag_id)));
+    }
+
+    note::Column::Id.in_subquery(
+        Query::select()
+            .column(note_tag::Column::NoteId)
+            .from(note_tag::Entity)
+            .cond_where(cond)
+            .to_owned(),
+    )
+}
+
+/// Notes having a tag whose name contains `keyword`.
+pub fn tag_name_filter(keyword: &str) -> SimpleExpr {
+    let ones = Query::select()
+        .column(tag_one::Column::Id)
+        .from(tag_one::Entity)
+        .and_where(tag_one::Column::Name.contains(keyword))
+        .to_owned();
+    let twos = Query::select()
+        .column(tag_two::Column::Id)
+        .from(tag_two::Entity)
+        .and_where(tag_two::Column::Name.contains(keyword))
+        .to_owned();
+
+    note::Column::Id.in_subquery(
+        Query::select()
+            .column(note_tag::Column::NoteId)
+            .from(note_tag::Entity)
+            .cond_where(
+                Cond::any()
+                    .add(Cond::all().add(note_tag::Column::Level.eq(1)).add(note_tag::Column::TagId.in_subquery(ones)))
+                    .add(Cond::all().add(note_tag::Column::Level.eq(2)).add(note_tag::Column::TagId.in_subquery(twos))),
+            )
+            .to_owned(),
+    )
+}
+
 pub async fn list_tags_one(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<Vec<TagOneDto>>> {
@@ -113,6 +376,24 @@ pub async fn delete_tags(
     // Assuming keys are IDs.
     // If we delete a Tag1, children Tag2s cascade? (Db constraint usually)
     // We'll try delete from both for now.
+
+    // Detach the tags from notes first, including children of deleted first-level tags
+    let children: Vec<i32> = tag_two::Entity::find()
+        .filter(tag_two::Column::TagOneId.is_in(keys.clone()))
+        .all(&state.db)
+        .await
+        .unwrap_or(vec![])
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+    let _ = note_tag::Entity::delete_many()
+        .filter(
+            Cond::any()
+                .add(note_tag::Column::TagId.is_in(keys.clone()))
+                .add(Cond::all().add(note_tag::Column::Level.eq(2)).add(note_tag::Column::TagId.is_in(children)))
+        )
+        .exec(&state.db)
+        .await;
     
     let _ = tag_two::Entity::delete_many()
         .filter(tag_two::Column::Id.is_in(keys.clone()))
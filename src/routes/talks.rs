@@ -3,8 +3,10 @@ use sea_orm::{EntityTrait, Set, QueryOrder};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use crate::entity::talk;
-use crate::routes::AppState;
+use crate::routes::{AppState, comments, reactions};
+use crate::routes::reactions::ReactionCounts;
 use crate::utils::ApiResponse;
+use crate::events::DomainEvent;
 
 #[derive(Serialize)]
 pub struct TalkDto {
@@ -17,13 +19,17 @@ pub struct TalkDto {
     pub created_at: String,
     #[serde(rename = "updateTime")]
     pub updated_at: String,
+    pub reactions: ReactionCounts,
 }
 
 pub async fn list_talks(
     State(state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<Vec<TalkDto>>> {
     let talks = talk::Entity::find().order_by_desc(talk::Column::CreatedAt).all(&state.db).await.unwrap_or(vec![]);
+    let ids: Vec<i32> = talks.iter().map(|t| t.id).collect();
+    let mut reaction_counts = reactions::load_counts(&state.db, reactions::TARGET_TALK, &ids).await.unwrap_or_default();
     let dtos = talks.into_iter().map(|t| TalkDto {
+        reactions: reaction_counts.remove(&t.id).unwrap_or_default(),
         id: t.id,
         title: t.title.unwrap_or_default(),
         content: t.content,
@@ -51,7 +57,8 @@ pub async fn create_talk(
         updated_at: Set(chrono::Utc::now().naive_utc()),
         ..Default::default()
     };
-    talk::Entity::insert(t).exec(&state.db).await.unwrap();
+    let res = talk::Entity::insert(t).exec(&state.db).await.unwrap();
+    state.events.publish(DomainEvent::TalkCreated { id: res.last_insert_id });
     Json(ApiResponse::success("Created".to_string()))
 }
 
@@ -60,6 +67,13 @@ pub async fn delete_talk(
     Path(id): Path<i32>,
 ) -> Json<ApiResponse<String>> {
     talk::Entity::delete_by_id(id).exec(&state.db).await.unwrap();
+    if let Err(e) = reactions::delete_for(&state.db, reactions::TARGET_TALK, &[id]).await {
+        return Json(ApiResponse::error(&format!("Error: {}", e)));
+    }
+    if let Err(e) = comments::delete_for(&state.db, comments::TARGET_TALK, &[id]).await {
+        return Json(ApiResponse::error(&format!("Error: {}", e)));
+    }
+    state.events.publish(DomainEvent::TalkDeleted { id });
     Json(ApiResponse::success("Deleted".to_string()))
 }
 
@@ -80,6 +94,7 @@ pub async fn update_talk(
         active_model.updated_at = Set(chrono::Utc::now().naive_utc());
         
         talk::Entity::update(active_model).exec(&state.db).await.unwrap();
+        state.events.publish(DomainEvent::TalkUpdated { id });
         Json(ApiResponse::success("Updated".to_string()))
     } else {
         Json(ApiResponse { code: 404, message: "Talk not found".to_string(), data: String::default() })
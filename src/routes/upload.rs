@@ -5,6 +5,7 @@ use axum::{
 use std::sync::Arc;
 use crate::routes::AppState;
 use crate::utils::ApiResponse;
+use crate::events::DomainEvent;
 use std::path::Path;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
@@ -33,7 +34,7 @@ pub async fn upload_image(
 
              if let Ok(data) = field.bytes().await {
                  if let Ok(mut file) = fs::File::create(&file_path).await {
-                     if let Ok(_) = file.write_all(&data).await {
+                     if file.write_all(&data).await.is_ok() {
                          // Use relative path matching the ServeDir route
                          let url = format!("/api/protect/download/{}", new_name);
                          
@@ -42,7 +43,9 @@ pub async fn upload_image(
                              image_url: Set(url.clone()),
                              ..Default::default()
                          };
-                         let _ = new_image.insert(&state.db).await;
+                         if let Ok(saved) = new_image.insert(&state.db).await {
+                             state.events.publish(DomainEvent::ImageUploaded { id: saved.image_key, url: url.clone() });
+                         }
 
                          return Json(ApiResponse::success(url));
                      }
@@ -73,6 +76,7 @@ pub async fn delete_images(
     Json(urls): Json<Vec<String>>,
 ) -> Json<ApiResponse<String>> {
     let upload_dir = "/opt/memory_blog_rust/uploads";
+    let mut deleted = Vec::new();
     for url in urls {
         // Find in DB
         if let Ok(Some(img)) = image::Entity::find()
@@ -82,13 +86,8 @@ pub async fn delete_images(
         {
             // Delete file logic: Extract filename from URL
             // Support both old /upload/ and new /download/ formats
-            let filename_opt = if let Some(part) = url.split("/upload/").nth(1) {
-                Some(part) 
-            } else if let Some(part) = url.split("/download/").nth(1) {
-                Some(part)
-            } else {
-                None
-            };
+            let filename_opt = url.split("/upload/").nth(1)
+                .or_else(|| url.split("/download/").nth(1));
 
             if let Some(filename) = filename_opt {
                  let path = Path::new(upload_dir).join(filename);
@@ -96,8 +95,13 @@ pub async fn delete_images(
             }
             
             // Delete from DB
-            let _ = image::Entity::delete_by_id(img.image_key).exec(&state.db).await;
+            if image::Entity::delete_by_id(img.image_key).exec(&state.db).await.is_ok() {
+                deleted.push(url);
+            }
         }
     }
+    if !deleted.is_empty() {
+        state.events.publish(DomainEvent::ImagesDeleted { urls: deleted });
+    }
     Json(ApiResponse::success("Deleted".to_string()))
 }
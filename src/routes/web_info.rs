@@ -1,5 +1,5 @@
 use axum::{Json, extract::State};
-use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, ActiveModelTrait, Set};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, ActiveModelTrait, Set, ConnectionTrait};
 use std::sync::Arc;
 use crate::entity::{web_info, user};
 use crate::routes::AppState;
@@ -76,6 +76,68 @@ pub struct WebSettingPayload {
     pub netease_cookies: Option<String>,
     #[serde(rename = "githubToken")]
     pub github_token: Option<String>,
+
+    // Spam filter blocklists, one keyword / regex per line
+    #[serde(rename = "spamKeywords")]
+    pub spam_keywords: Option<String>,
+    #[serde(rename = "spamPatterns")]
+    pub spam_patterns: Option<String>,
+
+    // Outgoing mail for notifications
+    #[serde(rename = "smtpHost")]
+    pub smtp_host: Option<String>,
+    #[serde(rename = "smtpPort")]
+    pub smtp_port: Option<String>,
+    // "tls" (default), "starttls" or "none"
+    #[serde(rename = "smtpSecurity")]
+    pub smtp_security: Option<String>,
+    #[serde(rename = "smtpUsername")]
+    pub smtp_username: Option<String>,
+    #[serde(rename = "smtpPassword")]
+    pub smtp_password: Option<String>,
+    #[serde(rename = "smtpFrom")]
+    pub smtp_from: Option<String>,
+    // Admin notifications go here, socialEmail when empty
+    #[serde(rename = "notifyEmail")]
+    pub notify_email: Option<String>,
+}
+
+// Site-wide metadata used when the backend renders pages and feeds itself
+#[derive(Clone, Debug, Default)]
+pub struct SiteMeta {
+    pub title: String,
+    pub author: String,
+    pub description: String,
+    pub avatar: String,
+    pub base_url: String, // blogDomain with a scheme and without trailing slash
+}
+
+/// Normalises `blogDomain` into an absolute base URL.
+pub fn base_url(domain: &str) -> String {
+    let domain = domain.trim().trim_end_matches('/');
+    if domain.is_empty() {
+        String::new()
+    } else if domain.starts_with("http://") || domain.starts_with("https://") {
+        domain.to_string()
+    } else {
+        format!("https://{}", domain)
+    }
+}
+
+pub async fn load_site_meta<C: ConnectionTrait>(db: &C) -> SiteMeta {
+    let infos = web_info::Entity::find().all(db).await.unwrap_or(vec![]);
+
+    let get_val = |k: &str| -> String {
+        infos.iter().find(|i| i.key_name == k).map(|i| i.value.clone()).unwrap_or("".to_string())
+    };
+
+    SiteMeta {
+        title: get_val("blog_title"),
+        author: get_val("author"),
+        description: get_val("blogDescription"),
+        avatar: get_val("avatar"),
+        base_url: base_url(&get_val("blogDomain")),
+    }
 }
 
 pub async fn get_web_settings(
@@ -91,13 +153,6 @@ pub async fn get_web_settings(
         infos.iter().find(|i| i.key_name == key).map(|i| i.value.clone())
     };
 
-    let user = user::Entity::find_by_id(1).one(&state.db).await.unwrap_or(None);
-    let (u_acc, u_pass) = if let Some(u) = user {
-        (Some("".to_string()), Some("".to_string()))
-    } else {
-        (Some("".to_string()), Some("".to_string()))
-    };
-
     let payload = WebSettingPayload {
         blog_title: get_val("blog_title"),
         blog_author: get_val("author"),
@@ -105,8 +160,9 @@ pub async fn get_web_settings(
         blog_description: get_direct("blogDescription"),
         blog_icp: get_val("icp"),
         
-        user_account: u_acc,
-        user_password: u_pass,
+        // Credentials are never sent back; the fields stay for the settings form
+        user_account: Some("".to_string()),
+        user_password: Some("".to_string()),
         user_avatar: get_val("avatar"),
         user_talk: get_val("talk"),
         
@@ -119,6 +175,17 @@ pub async fn get_web_settings(
         openai_token: get_direct("openAiToken"),
         netease_cookies: get_direct("neteaseCookies"),
         github_token: get_direct("githubToken"),
+
+        spam_keywords: get_direct("spamKeywords"),
+        spam_patterns: get_direct("spamPatterns"),
+
+        smtp_host: get_direct("smtpHost"),
+        smtp_port: get_direct("smtpPort"),
+        smtp_security: get_direct("smtpSecurity"),
+        smtp_username: get_direct("smtpUsername"),
+        smtp_password: get_direct("smtpPassword"),
+        smtp_from: get_direct("smtpFrom"),
+        notify_email: get_direct("notifyEmail"),
     };
 
     Json(ApiResponse::success(payload))
@@ -216,6 +283,17 @@ pub async fn update_web_info(
     if let Some(v) = payload.netease_cookies { map.insert("neteaseCookies", v); }
     if let Some(v) = payload.github_token { map.insert("githubToken", v); }
 
+    if let Some(v) = payload.spam_keywords { map.insert("spamKeywords", v); }
+    if let Some(v) = payload.spam_patterns { map.insert("spamPatterns", v); }
+
+    if let Some(v) = payload.smtp_host { map.insert("smtpHost", v); }
+    if let Some(v) = payload.smtp_port { map.insert("smtpPort", v); }
+    if let Some(v) = payload.smtp_security { map.insert("smtpSecurity", v); }
+    if let Some(v) = payload.smtp_username { map.insert("smtpUsername", v); }
+    if let Some(v) = payload.smtp_password { map.insert("smtpPassword", v); }
+    if let Some(v) = payload.smtp_from { map.insert("smtpFrom", v); }
+    if let Some(v) = payload.notify_email { map.insert("notifyEmail", v); }
+
     for (k, v) in map {
         let entry = web_info::Entity::find()
             .filter(web_info::Column::KeyName.eq(k))
@@ -0,0 +1,263 @@
+use axum::{Json, extract::{Path, Query, State}};
+use sea_orm::{EntityTrait, ColumnTrait, QueryFilter, QueryOrder, ActiveModelTrait, Set, Condition, PaginatorTrait};
+use sea_orm::sea_query::Expr;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::entity::{webhook, webhook_delivery};
+use crate::routes::AppState;
+use crate::utils::{ApiResponse, format_datetime};
+use crate::jobs;
+use crate::webhooks::{self, EVENTS, STATUS_PENDING};
+
+const DELIVERY_PAGE_SIZE: u64 = 20;
+
+#[derive(Deserialize)]
+pub struct UpsertWebhook {
+    pub name: String,
+    pub url: String,
+    // Generated when left empty on create, kept when left empty on update
+    pub secret: Option<String>,
+    // Event names, or ["*"] for all
+    pub events: Vec<String>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct DeliveryQuery {
+    #[serde(rename = "webhookKey")]
+    pub webhook_id: Option<i32>,
+    pub status: Option<String>,
+    pub page: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct WebhookDto {
+    #[serde(rename = "webhookKey")]
+    pub id: i32,
+    pub name: String,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+    pub enabled: bool,
+    #[serde(rename = "createTime")]
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct DeliveryDto {
+    #[serde(rename = "deliveryKey")]
+    pub id: i32,
+    #[serde(rename = "webhookKey")]
+    pub webhook_id: i32,
+    pub event: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i32,
+    #[serde(rename = "responseCode")]
+    pub response_code: Option<i32>,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename = "nextAttemptTime")]
+    pub next_attempt_at: String,
+    #[serde(rename = "deliveredTime")]
+    pub delivered_at: Option<String>,
+    #[serde(rename = "createTime")]
+    pub created_at: String,
+}
+
+fn map_webhook(w: webhook::Model) -> WebhookDto {
+    WebhookDto {
+        id: w.id,
+        name: w.name,
+        url: w.url,
+        secret: w.secret,
+        events: w.events.split(',').map(|e| e.trim().to_string()).filter(|e| !e.is_empty()).collect(),
+        enabled: w.enabled,
+        created_at: format_datetime(w.created_at),
+    }
+}
+
+fn map_delivery(d: webhook_delivery::Model) -> DeliveryDto {
+    DeliveryDto {
+        id: d.id,
+        webhook_id: d.webhook_id,
+        event: d.event,
+        payload: d.payload,
+        status: d.status,
+        attempts: d.attempts,
+        response_code: d.response_code,
+        last_error: d.last_error,
+        next_attempt_at: format_datetime(d.next_attempt_at),
+        delivered_at: d.delivered_at.map(format_datetime),
+        created_at: format_datetime(d.created_at),
+    }
+}
+
+/// Checks the payload and returns the normalised events column.
+fn validate(payload: &UpsertWebhook) -> Result<String, &'static str> {
+    if payload.name.trim().is_empty() || payload.name.chars().count() > 64 {
+        return Err("Name must be 1-64 characters");
+    }
+    let url = payload.url.trim();
+    if url.len() > 512 || !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err("URL must be an http(s) URL");
+    }
+    if payload.events.is_empty() {
+        return Err("Subscribe to at least one event");
+    }
+    if payload.events.iter().any(|e| e == "*") {
+        return Ok("*".to_string());
+    }
+    if payload.events.iter().any(|e| !EVENTS.contains(&e.as_str())) {
+        return Err("Unknown event");
+    }
+    Ok(payload.events.join(","))
+}
+
+// GET /api/protected/webhooks
+pub async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+) -> Json<ApiResponse<Vec<WebhookDto>>> {
+    match webhook::Entity::find().order_by_asc(webhook::Column::Id).all(&state.db).await {
+        Ok(w) => Json(ApiResponse::success(w.into_iter().map(map_webhook).collect())),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+// POST /api/protected/webhooks
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<UpsertWebhook>,
+) -> Json<ApiResponse<String>> {
+    let events = match validate(&payload) {
+        Ok(v) => v,
+        Err(msg) => return Json(ApiResponse::error(msg)),
+    };
+    let secret = payload.secret
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+    let now = chrono::Utc::now().naive_utc();
+    let hook = webhook::ActiveModel {
+        name: Set(payload.name.trim().to_string()),
+        url: Set(payload.url.trim().to_string()),
+        secret: Set(secret),
+        events: Set(events),
+        enabled: Set(payload.enabled.unwrap_or(true)),
+        created_at: Set(now),
+        updated_at: Set(now),
+        ..Default::default()
+    };
+    match hook.insert(&state.db).await {
+        Ok(_) => Json(ApiResponse::success("Created".to_string())),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+// POST /api/protected/webhooks/:id
+pub async fn update_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(payload): Json<UpsertWebhook>,
+) -> Json<ApiResponse<String>> {
+    let events = match validate(&payload) {
+        Ok(v) => v,
+        Err(msg) => return Json(ApiResponse::error(msg)),
+    };
+    let hook = match webhook::Entity::find_by_id(id).one(&state.db).await {
+        Ok(Some(h)) => h,
+        Ok(None) => return Json(ApiResponse::error("Not found")),
+        Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+    };
+
+    let mut active: webhook::ActiveModel = hook.into();
+    active.name = Set(payload.name.trim().to_string());
+    active.url = Set(payload.url.trim().to_string());
+    active.events = Set(events);
+    if let Some(secret) = payload.secret.filter(|s| !s.trim().is_empty()) {
+        active.secret = Set(secret);
+    }
+    if let Some(enabled) = payload.enabled {
+        active.enabled = Set(enabled);
+    }
+    active.updated_at = Set(chrono::Utc::now().naive_utc());
+    match active.update(&state.db).await {
+        Ok(_) => Json(ApiResponse::success("Updated".to_string())),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+// DELETE /api/protected/webhooks, their delivery logs go too
+pub async fn delete_webhooks(
+    State(state): State<Arc<AppState>>,
+    Json(keys): Json<Vec<i32>>,
+) -> Json<ApiResponse<String>> {
+    match webhook::Entity::delete_many()
+        .filter(webhook::Column::Id.is_in(keys))
+        .exec(&state.db)
+        .await {
+        Ok(_) => Json(ApiResponse::success("Deleted".to_string())),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+// GET /api/protected/webhooks/deliveries?webhookKey=1&status=failed&page=1
+pub async fn list_deliveries(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DeliveryQuery>,
+) -> Json<ApiResponse<Vec<DeliveryDto>>> {
+    let mut condition = Condition::all();
+    if let Some(id) = query.webhook_id {
+        condition = condition.add(webhook_delivery::Column::WebhookId.eq(id));
+    }
+    if let Some(status) = query.status.filter(|s| !s.is_empty()) {
+        condition = condition.add(webhook_delivery::Column::Status.eq(status));
+    }
+
+    let deliveries = webhook_delivery::Entity::find()
+        .filter(condition)
+        .order_by_desc(webhook_delivery::Column::Id)
+        .paginate(&state.db, DELIVERY_PAGE_SIZE)
+        .fetch_page(query.page.unwrap_or(1).max(1) - 1)
+        .await;
+
+    match deliveries {
+        Ok(d) => Json(ApiResponse::success(d.into_iter().map(map_delivery).collect())),
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+// POST /api/protected/webhooks/deliveries/:id/redeliver, queues a sent or failed delivery again with a fresh retry budget
+pub async fn redeliver(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> Json<ApiResponse<String>> {
+    let res = webhook_delivery::Entity::update_many()
+        .col_expr(webhook_delivery::Column::Status, Expr::value(STATUS_PENDING))
+        .col_expr(webhook_delivery::Column::Attempts, Expr::value(0))
+        .col_expr(webhook_delivery::Column::NextAttemptAt, Expr::value(chrono::Utc::now().naive_utc()))
+        .filter(webhook_delivery::Column::Id.eq(id))
+        // A pending one already has a job waiting
+        .filter(webhook_delivery::Column::Status.ne(STATUS_PENDING))
+        .exec(&state.db)
+        .await;
+
+    match res {
+        Ok(r) if r.rows_affected == 0 => return Json(ApiResponse::error("Not found or already queued")),
+        Ok(_) => {}
+        Err(e) => return Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+    match webhooks::queue_delivery(&state.db, id).await {
+        Ok(_) => {
+            jobs::wake(&state);
+            Json(ApiResponse::success("Queued".to_string()))
+        }
+        Err(e) => Json(ApiResponse::error(&format!("Error: {}", e))),
+    }
+}
+
+// GET /api/protected/webhooks/events
+pub async fn list_events() -> Json<ApiResponse<Vec<String>>> {
+    Json(ApiResponse::success(EVENTS.iter().map(|e| e.to_string()).collect()))
+}
+
@@ -0,0 +1,79 @@
+use sea_orm::{ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Set};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use crate::entity::note;
+use crate::events::DomainEvent;
+use crate::jobs;
+use crate::routes::AppState;
+
+// One job per scheduled note, due at its `publish_at`
+pub const JOB_PUBLISH: &str = "note.publish";
+
+/// Queues (or moves) the publish job of a scheduled note. Notes in any other
+/// state are left alone; a job left over from an earlier schedule finds nothing
+/// to do when it runs.
+pub async fn schedule<C: ConnectionTrait>(db: &C, n: &note::Model) -> Result<(), DbErr> {
+    if n.status.as_deref() != Some("scheduled") {
+        return Ok(());
+    }
+    let run_at = n.publish_at.unwrap_or_else(|| chrono::Utc::now().naive_utc());
+    let key = format!("{}:{}", JOB_PUBLISH, n.id);
+    jobs::enqueue_at(db, JOB_PUBLISH, json!({ "noteKey": n.id }), &key, run_at).await.map(|_| ())
+}
+
+/// Makes sure every scheduled note has its publish job, e.g. notes scheduled
+/// before jobs existed. Returns how many notes were looked at.
+pub async fn queue_scheduled<C: ConnectionTrait>(db: &C) -> Result<usize, DbErr> {
+    let scheduled = note::Entity::find()
+        .filter(note::Column::Status.eq("scheduled"))
+        .all(db)
+        .await?;
+    for n in &scheduled {
+        schedule(db, n).await?;
+    }
+    Ok(scheduled.len())
+}
+
+/// Flips a scheduled note to published and returns it as it is now. The note's
+/// creation time becomes its publish time so it sorts where readers expect.
+pub async fn publish<C: ConnectionTrait>(db: &C, n: note::Model) -> Result<note::Model, DbErr> {
+    let published_at = n.publish_at.unwrap_or(n.created_at);
+    let mut active: note::ActiveModel = n.into();
+    active.status = Set(Some("published".to_string()));
+    active.is_public = Set(true);
+    active.created_at = Set(published_at);
+    active.update(db).await
+}
+
+/// Job handler for `JOB_PUBLISH`.
+pub async fn run_job(state: Arc<AppState>, payload: Value) -> Result<(), String> {
+    let id = payload["noteKey"].as_i64().ok_or("Missing noteKey")? as i32;
+    let n = note::Entity::find_by_id(id).one(&state.db).await.map_err(|e| e.to_string())?;
+    // Deleted, published by hand or turned back into a draft since it was queued
+    let Some(n) = n.filter(|n| n.status.as_deref() == Some("scheduled")) else {
+        return Ok(());
+    };
+    // Moved to a later time while this job was already running. Rescheduling queued
+    // a new job for that time, so this one is done rather than retried into the ground
+    if n.publish_at.is_some_and(|at| at > chrono::Utc::now().naive_utc()) {
+        return Ok(());
+    }
+
+    let published = publish(&state.db, n).await.map_err(|e| e.to_string())?;
+    tracing::info!("Published scheduled note {}", published.id);
+    state.events.publish(DomainEvent::NotePublished(published));
+    Ok(())
+}
+
+/// Event bus subscriber keeping publish jobs in line with saved notes.
+pub async fn on_event(state: Arc<AppState>, event: Arc<DomainEvent>) {
+    match event.as_ref() {
+        DomainEvent::NoteCreated(n) | DomainEvent::NoteUpdated { note: n, .. } => {
+            match schedule(&state.db, n).await {
+                Ok(()) => jobs::wake(&state),
+                Err(e) => tracing::error!("Scheduling note {} failed: {}", n.id, e),
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,93 @@
+use sea_orm::{ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Set};
+use crate::entity::{note, note_slug_redirect};
+
+const MAX_SLUG_LEN: usize = 80;
+
+/// Turns a title into a URL slug. Non-Latin text is transliterated first,
+/// so Chinese titles come out as pinyin ("你好 Rust" -> "ni-hao-rust").
+pub fn slugify(title: &str) -> String {
+    let ascii = deunicode::deunicode(title);
+    let mut slug = String::new();
+    for c in ascii.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let mut slug = slug.trim_end_matches('-').to_string();
+    if slug.len() > MAX_SLUG_LEN {
+        slug.truncate(MAX_SLUG_LEN);
+        slug = slug.trim_end_matches('-').to_string();
+    }
+    if slug.is_empty() { "note".to_string() } else { slug }
+}
+
+/// Whether `slug` is held by another note, either as its current slug or as a redirect.
+pub async fn is_taken<C: ConnectionTrait>(db: &C, slug: &str, note_id: Option<i32>) -> Result<bool, DbErr> {
+    let mut current = note::Entity::find().filter(note::Column::Slug.eq(slug));
+    let mut redirect = note_slug_redirect::Entity::find().filter(note_slug_redirect::Column::Slug.eq(slug));
+    if let Some(id) = note_id {
+        current = current.filter(note::Column::Id.ne(id));
+        redirect = redirect.filter(note_slug_redirect::Column::NoteId.ne(id));
+    }
+
+    Ok(current.one(db).await?.is_some() || redirect.one(db).await?.is_some())
+}
+
+/// Slug for `title` that no other note uses, adding `-2`, `-3`, ... on collision.
+pub async fn unique_slug<C: ConnectionTrait>(db: &C, title: &str, note_id: Option<i32>) -> Result<String, DbErr> {
+    let base = slugify(title);
+    let mut candidate = base.clone();
+    let mut n = 2;
+    while is_taken(db, &candidate, note_id).await? {
+        candidate = format!("{}-{}", base, n);
+        n += 1;
+    }
+    Ok(candidate)
+}
+
+/// Records `old` as a redirect to `note_id` and drops any redirect for the slug now in use.
+pub async fn record_rename<C: ConnectionTrait>(db: &C, note_id: i32, old: &str, new: &str) -> Result<(), DbErr> {
+    note_slug_redirect::Entity::delete_many()
+        .filter(note_slug_redirect::Column::Slug.eq(new))
+        .filter(note_slug_redirect::Column::NoteId.eq(note_id))
+        .exec(db)
+        .await?;
+
+    let exists = note_slug_redirect::Entity::find()
+        .filter(note_slug_redirect::Column::Slug.eq(old))
+        .one(db)
+        .await?
+        .is_some();
+    if !exists {
+        note_slug_redirect::ActiveModel {
+            slug: Set(old.to_string()),
+            note_id: Set(note_id),
+            created_at: Set(chrono::Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Gives every note created before slugs existed one generated from its title.
+pub async fn backfill_missing<C: ConnectionTrait>(db: &C) -> Result<u64, DbErr> {
+    let notes = note::Entity::find()
+        .filter(note::Column::Slug.is_null())
+        .all(db)
+        .await?;
+
+    let mut count = 0;
+    for n in notes {
+        let slug = unique_slug(db, &n.title, Some(n.id)).await?;
+        let mut active: note::ActiveModel = n.into();
+        active.slug = Set(Some(slug));
+        active.update(db).await?;
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,312 @@
+use hmac::{Hmac, Mac};
+use regex::{Regex, RegexBuilder};
+use sea_orm::{sea_query::{Expr, OnConflict}, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Set};
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use crate::entity::{spam_token, web_info};
+use crate::text::terms;
+
+// Form tokens younger than this were filled in by a script
+const MIN_FILL_SECS: i64 = 3;
+const MAX_TOKEN_AGE_SECS: i64 = 2 * 3600;
+const SUSPICIOUS_LINKS: usize = 3;
+const BLOCKED_LINKS: usize = 8;
+const SPAM_PROBABILITY: f64 = 0.9;
+// The classifier stays quiet until it has seen this many messages of each kind
+const MIN_TRAINING_DOCS: i32 = 10;
+const INTERESTING_TOKENS: usize = 15;
+const MAX_TOKENS: usize = 300;
+const DOCUMENTS_TOKEN: &str = "__documents__";
+
+pub const LABEL_SPAM: &str = "spam";
+pub const LABEL_HAM: &str = "ham";
+
+/// A public submission as seen by the filter.
+pub struct SpamInput<'a> {
+    pub author: &'a str,
+    pub email: &'a str,
+    pub website: Option<&'a str>,
+    pub content: &'a str,
+    // Hidden form field that only bots fill in
+    pub honeypot: Option<&'a str>,
+    pub form_token: Option<&'a str>,
+}
+
+impl SpamInput<'_> {
+    fn text(&self) -> String {
+        format!("{} {} {} {}", self.author, self.email, self.website.unwrap_or(""), self.content)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Ham,
+    // Accepted but held back as spam for the admin to review
+    Suspicious(String),
+    // Refused outright
+    Block(String),
+}
+
+/// Data the checks need that has to be loaded up front.
+pub struct SpamContext {
+    pub keywords: Vec<String>,
+    pub patterns: Vec<Regex>,
+    // Bayes spam probability, None while the classifier is untrained
+    pub spam_probability: Option<f64>,
+}
+
+/// One stage of the pipeline. Stages are synchronous; anything they need from the
+/// database goes into `SpamContext` first.
+pub trait SpamCheck: Send + Sync {
+    fn check(&self, input: &SpamInput, token_age: Option<i64>, ctx: &SpamContext) -> Verdict;
+}
+
+pub struct Honeypot;
+
+impl SpamCheck for Honeypot {
+    fn check(&self, input: &SpamInput, _: Option<i64>, _: &SpamContext) -> Verdict {
+        match input.honeypot {
+            Some(v) if !v.trim().is_empty() => Verdict::Block("honeypot filled".to_string()),
+            _ => Verdict::Ham,
+        }
+    }
+}
+
+pub struct FillTime;
+
+impl SpamCheck for FillTime {
+    fn check(&self, _: &SpamInput, token_age: Option<i64>, _: &SpamContext) -> Verdict {
+        match token_age {
+            None => Verdict::Suspicious("missing or invalid form token".to_string()),
+            Some(age) if age < MIN_FILL_SECS => Verdict::Block("form submitted too quickly".to_string()),
+            Some(age) if age > MAX_TOKEN_AGE_SECS => Verdict::Suspicious("form token expired".to_string()),
+            Some(_) => Verdict::Ham,
+        }
+    }
+}
+
+pub struct Blocklist;
+
+impl SpamCheck for Blocklist {
+    fn check(&self, input: &SpamInput, _: Option<i64>, ctx: &SpamContext) -> Verdict {
+        let text = input.text();
+        let lowered = text.to_lowercase();
+        if let Some(k) = ctx.keywords.iter().find(|k| lowered.contains(k.as_str())) {
+            return Verdict::Block(format!("blocked keyword: {}", k));
+        }
+        if let Some(p) = ctx.patterns.iter().find(|p| p.is_match(&text)) {
+            return Verdict::Block(format!("blocked pattern: {}", p.as_str()));
+        }
+        Verdict::Ham
+    }
+}
+
+pub struct LinkCount;
+
+impl SpamCheck for LinkCount {
+    fn check(&self, input: &SpamInput, _: Option<i64>, _: &SpamContext) -> Verdict {
+        let content = input.content.to_lowercase();
+        let links = content.matches("http://").count() + content.matches("https://").count() + content.matches("www.").count();
+        if links >= BLOCKED_LINKS {
+            Verdict::Block(format!("{} links", links))
+        } else if links >= SUSPICIOUS_LINKS {
+            Verdict::Suspicious(format!("{} links", links))
+        } else {
+            Verdict::Ham
+        }
+    }
+}
+
+pub struct Bayes;
+
+impl SpamCheck for Bayes {
+    fn check(&self, _: &SpamInput, _: Option<i64>, ctx: &SpamContext) -> Verdict {
+        match ctx.spam_probability {
+            Some(p) if p >= SPAM_PROBABILITY => Verdict::Suspicious(format!("classifier score {:.2}", p)),
+            _ => Verdict::Ham,
+        }
+    }
+}
+
+/// The spam-check pipeline plus the key used to sign form tokens.
+pub struct SpamFilter {
+    secret: String,
+    checks: Vec<Box<dyn SpamCheck>>,
+    // `spamPatterns` as last read, with its compiled expressions
+    patterns: Mutex<Option<(String, Vec<Regex>)>>,
+}
+
+impl Default for SpamFilter {
+    fn default() -> Self {
+        Self::new(vec![
+            Box::new(Honeypot),
+            Box::new(FillTime),
+            Box::new(Blocklist),
+            Box::new(LinkCount),
+            Box::new(Bayes),
+        ])
+    }
+}
+
+fn now_secs() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+impl SpamFilter {
+    pub fn new(checks: Vec<Box<dyn SpamCheck>>) -> Self {
+        // Tokens only need to survive while the process runs, so the key is never stored
+        let secret = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        Self { secret, checks, patterns: Mutex::new(None) }
+    }
+
+    fn sign(&self, issued: i64) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(issued.to_string().as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Token handed out when a form is shown; it records when that happened.
+    pub fn issue_token(&self) -> String {
+        self.issue_token_at(now_secs())
+    }
+
+    pub fn issue_token_at(&self, issued: i64) -> String {
+        format!("{}.{}", issued, self.sign(issued))
+    }
+
+    /// Seconds since the token was issued, or None if it is not one of ours.
+    pub fn token_age(&self, token: &str) -> Option<i64> {
+        let (issued, signature) = token.split_once('.')?;
+        let issued: i64 = issued.parse().ok()?;
+        let expected = self.sign(issued);
+        // Both sides are hex of the same length, compare without short-circuiting
+        let same = expected.len() == signature.len()
+            && expected.bytes().zip(signature.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0;
+        same.then(|| now_secs() - issued)
+    }
+
+    /// Runs every check against an already loaded context.
+    pub fn judge(&self, input: &SpamInput, ctx: &SpamContext) -> Verdict {
+        let token_age = input.form_token.and_then(|t| self.token_age(t));
+        let mut reasons = Vec::new();
+        for check in &self.checks {
+            match check.check(input, token_age, ctx) {
+                Verdict::Block(reason) => return Verdict::Block(reason),
+                Verdict::Suspicious(reason) => reasons.push(reason),
+                Verdict::Ham => {}
+            }
+        }
+        if reasons.is_empty() { Verdict::Ham } else { Verdict::Suspicious(reasons.join("; ")) }
+    }
+
+    pub async fn evaluate<C: ConnectionTrait>(&self, db: &C, input: &SpamInput<'_>) -> Result<Verdict, DbErr> {
+        let infos = web_info::Entity::find()
+            .filter(web_info::Column::KeyName.is_in(["spamKeywords", "spamPatterns"]))
+            .all(db)
+            .await?;
+        let ctx = SpamContext {
+            keywords: setting_lines(&infos, "spamKeywords").iter().map(|k| k.to_lowercase()).collect(),
+            patterns: self.compiled_patterns(&infos),
+            spam_probability: spam_probability(db, &input.text()).await?,
+        };
+        Ok(self.judge(input, &ctx))
+    }
+
+    /// Case-insensitive regexes for `spamPatterns`, compiled again only when the setting changes.
+    fn compiled_patterns(&self, infos: &[web_info::Model]) -> Vec<Regex> {
+        let raw = infos.iter().find(|i| i.key_name == "spamPatterns").map(|i| i.value.as_str()).unwrap_or("");
+        let mut cached = self.patterns.lock().unwrap();
+        match cached.as_ref() {
+            Some((source, compiled)) if source == raw => compiled.clone(),
+            _ => {
+                // Invalid expressions are skipped rather than breaking every submission
+                let compiled: Vec<Regex> = setting_lines(infos, "spamPatterns")
+                    .iter()
+                    .filter_map(|p| RegexBuilder::new(p).case_insensitive(true).build().ok())
+                    .collect();
+                *cached = Some((raw.to_string(), compiled.clone()));
+                compiled
+            }
+        }
+    }
+}
+
+fn tokens(text: &str) -> Vec<String> {
+    let mut t: Vec<String> = terms(text).into_iter().filter(|t| t.len() <= 64).collect();
+    t.sort();
+    t.truncate(MAX_TOKENS);
+    t
+}
+
+/// Lines of a `web_info` setting as written, blanks skipped.
+fn setting_lines(infos: &[web_info::Model], key: &str) -> Vec<String> {
+    infos.iter()
+        .find(|i| i.key_name == key)
+        .map(|i| i.value.lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Naive Bayes estimate that `text` is spam, combining the most telling tokens
+/// (Robinson's smoothing, so rare tokens stay close to neutral).
+pub async fn spam_probability<C: ConnectionTrait>(db: &C, text: &str) -> Result<Option<f64>, DbErr> {
+    let mut wanted = tokens(text);
+    wanted.push(DOCUMENTS_TOKEN.to_string());
+
+    let rows: HashMap<String, (i32, i32)> = spam_token::Entity::find()
+        .filter(spam_token::Column::Token.is_in(wanted))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|r| (r.token, (r.spam, r.ham)))
+        .collect();
+
+    let Some(&(spam_docs, ham_docs)) = rows.get(DOCUMENTS_TOKEN) else {
+        return Ok(None);
+    };
+    if spam_docs < MIN_TRAINING_DOCS || ham_docs < MIN_TRAINING_DOCS {
+        return Ok(None);
+    }
+
+    let mut scores: Vec<f64> = rows.iter()
+        .filter(|(token, _)| token.as_str() != DOCUMENTS_TOKEN)
+        .map(|(_, &(spam, ham))| {
+            let p_spam = spam.max(0) as f64 / spam_docs as f64;
+            let p_ham = ham.max(0) as f64 / ham_docs as f64;
+            let n = (spam.max(0) + ham.max(0)) as f64;
+            let raw = if p_spam + p_ham > 0.0 { p_spam / (p_spam + p_ham) } else { 0.5 };
+            ((0.5 + n * raw) / (1.0 + n)).clamp(0.01, 0.99)
+        })
+        .collect();
+    if scores.is_empty() {
+        return Ok(None);
+    }
+
+    scores.sort_by(|a, b| (b - 0.5).abs().total_cmp(&(a - 0.5).abs()));
+    scores.truncate(INTERESTING_TOKENS);
+    let log_odds: f64 = scores.iter().map(|p| (p / (1.0 - p)).ln()).sum();
+    Ok(Some(1.0 / (1.0 + (-log_odds).exp())))
+}
+
+/// Adds (`delta` = 1) or removes (`delta` = -1) one message from the classifier.
+pub async fn train<C: ConnectionTrait>(db: &C, text: &str, label: &str, delta: i32) -> Result<(), DbErr> {
+    let (spam, ham) = if label == LABEL_SPAM { (delta, 0) } else { (0, delta) };
+    let mut all: HashSet<String> = tokens(text).into_iter().collect();
+    all.insert(DOCUMENTS_TOKEN.to_string());
+
+    let rows = all.into_iter().map(|token| spam_token::ActiveModel {
+        token: Set(token),
+        spam: Set(spam.max(0)),
+        ham: Set(ham.max(0)),
+    });
+    spam_token::Entity::insert_many(rows)
+        .on_conflict(
+            OnConflict::column(spam_token::Column::Token)
+                .value(spam_token::Column::Spam, Expr::cust(format!("GREATEST(`spam` + {}, 0)", spam)))
+                .value(spam_token::Column::Ham, Expr::cust(format!("GREATEST(`ham` + {}, 0)", ham)))
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .map(|_| ())
+}
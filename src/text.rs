@@ -0,0 +1,50 @@
+use std::collections::HashSet;
+
+// Common words that carry no meaning on their own, e.g. in the word cloud
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "with", "from", "that", "this", "into", "your", "you", "are", "was",
+    "how", "what", "why", "use", "using", "not", "but", "its", "our", "can", "all", "one",
+    "我们", "一个", "什么", "怎么", "如何", "这个", "那个", "没有", "自己", "可以", "以及",
+];
+
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}')
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '+' || c == '#'
+}
+
+/// Candidate terms of one text: Latin words of 3+ letters and CJK character bigrams
+/// (there is no word segmenter, and bigrams approximate most Chinese words well enough).
+pub fn terms(text: &str) -> HashSet<String> {
+    let mut found = HashSet::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+
+    // The trailing space flushes whatever is still buffered
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if !is_cjk(c) && !cjk_run.is_empty() {
+            for pair in cjk_run.windows(2) {
+                let bigram: String = pair.iter().collect();
+                if !STOPWORDS.contains(&bigram.as_str()) {
+                    found.insert(bigram);
+                }
+            }
+            cjk_run.clear();
+        }
+        if !is_word_char(c) && !word.is_empty() {
+            if word.len() >= 3 && !STOPWORDS.contains(&word.as_str()) {
+                found.insert(word.clone());
+            }
+            word.clear();
+        }
+
+        if is_cjk(c) {
+            cjk_run.push(c);
+        } else if is_word_char(c) {
+            word.push(c.to_ascii_lowercase());
+        }
+    }
+    found
+}
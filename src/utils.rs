@@ -1,3 +1,4 @@
+use axum::http::{header, HeaderMap};
 use serde::Serialize;
 use sha2::{Sha256, Digest};
 
@@ -32,3 +33,49 @@ pub fn encrypt_password(input: &str) -> String {
     let result = hasher.finalize();
     hex::encode(result)
 }
+
+/// Formats a stored UTC timestamp in Beijing time, the format the frontend expects.
+pub fn format_datetime(dt: chrono::NaiveDateTime) -> String {
+    dt.and_utc()
+        .with_timezone(&chrono::FixedOffset::east_opt(8 * 3600).unwrap())
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Parses a timestamp sent by the frontend back to UTC. Accepts RFC 3339, or
+/// `YYYY-MM-DD HH:MM[:SS]` / `YYYY-MM-DDTHH:MM[:SS]` in Beijing time.
+pub fn parse_datetime(input: &str) -> Option<chrono::NaiveDateTime> {
+    let input = input.trim();
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(input) {
+        return Some(dt.naive_utc());
+    }
+
+    let local = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|fmt| chrono::NaiveDateTime::parse_from_str(input, fmt).ok())?;
+    Some(local - chrono::Duration::hours(8))
+}
+
+/// Strong ETag from arbitrary bytes.
+pub fn etag_for(data: &[u8]) -> String {
+    format!("\"{}\"", &hex::encode(Sha256::digest(data))[..32])
+}
+
+/// Whether the request's validators (`If-None-Match`, then `If-Modified-Since`)
+/// show the client already has this representation.
+pub fn is_not_modified(headers: &HeaderMap, etag: &str, last_modified: Option<chrono::NaiveDateTime>) -> bool {
+    if let Some(inm) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        return inm.split(',').any(|t| {
+            let t = t.trim();
+            t == "*" || t.trim_start_matches("W/") == etag
+        });
+    }
+
+    if let (Some(ims), Some(lm)) = (headers.get(header::IF_MODIFIED_SINCE).and_then(|v| v.to_str().ok()), last_modified) {
+        if let Ok(since) = chrono::DateTime::parse_from_rfc2822(ims) {
+            // HTTP dates have second precision
+            return lm.and_utc().timestamp() <= since.timestamp();
+        }
+    }
+    false
+}
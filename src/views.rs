@@ -0,0 +1,220 @@
+use axum::http::HeaderMap;
+use chrono::NaiveDate;
+use sea_orm::{sea_query::{Expr, OnConflict}, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Set, TransactionTrait};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use crate::entity::{note, note_view};
+use crate::routes::AppState;
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(15);
+// A request that fills the buffer past this triggers an early flush
+pub const FLUSH_THRESHOLD: usize = 500;
+
+#[derive(Default, Clone, Copy)]
+struct PendingView {
+    hits: i32,
+    new_visitor: bool, // first view of this note by this visitor today
+}
+
+struct Buffer {
+    day: NaiveDate,
+    salt: String,
+    seen: HashSet<(i32, String)>,
+    pending: HashMap<(i32, String, NaiveDate), PendingView>,
+}
+
+/// Buffers note views in memory until the next flush.
+///
+/// Visitors are identified by `sha256(salt + ip + user agent)`. The salt is random,
+/// only ever held in memory and replaced when the (Beijing) day changes, so the
+/// stored hashes can neither be reversed to an IP nor linked across days.
+pub struct ViewTracker {
+    buffer: Mutex<Buffer>,
+}
+
+impl Default for ViewTracker {
+    fn default() -> Self {
+        Self {
+            buffer: Mutex::new(Buffer {
+                day: today(),
+                salt: new_salt(),
+                seen: HashSet::new(),
+                pending: HashMap::new(),
+            }),
+        }
+    }
+}
+
+fn today() -> NaiveDate {
+    (chrono::Utc::now() + chrono::Duration::hours(8)).date_naive()
+}
+
+fn new_salt() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+/// Whether forwarding headers from `ip` are believed: loopback, where nginx runs,
+/// plus any addresses listed in `TRUSTED_PROXIES` (comma separated).
+fn is_trusted_proxy(ip: IpAddr) -> bool {
+    static CONFIGURED: OnceLock<Vec<IpAddr>> = OnceLock::new();
+    let configured = CONFIGURED.get_or_init(|| {
+        std::env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|v| v.trim().parse().ok())
+            .collect()
+    });
+    ip.is_loopback() || configured.contains(&ip)
+}
+
+/// Client address for rate limits and visitor hashes. Forwarding headers are
+/// only used when the request came through a trusted proxy; anyone else could
+/// put a different address in them on every request.
+pub fn client_ip(headers: &HeaderMap, peer: Option<SocketAddr>) -> String {
+    let Some(peer) = peer.map(|p| p.ip()) else {
+        return "unknown".to_string();
+    };
+    if !is_trusted_proxy(peer) {
+        return peer.to_string();
+    }
+
+    let parse = |v: &str| v.trim().parse::<IpAddr>().ok();
+    // nginx sets X-Real-IP itself; X-Forwarded-For is appended to whatever the
+    // client sent, so only its last hop was written by the proxy
+    let real_ip = headers.get("x-real-ip")
+        .and_then(|h| h.to_str().ok())
+        .and_then(parse);
+    let forwarded = headers.get("x-forwarded-for")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|v| v.rsplit(',').next())
+        .and_then(parse);
+
+    real_ip.or(forwarded).unwrap_or(peer).to_string()
+}
+
+impl Buffer {
+    // Replaces the salt once the day has changed
+    fn roll_over(&mut self) -> NaiveDate {
+        let day = today();
+        if day != self.day {
+            self.day = day;
+            self.salt = new_salt();
+            self.seen.clear();
+        }
+        day
+    }
+
+    fn visitor_hash(&self, ip: &str, user_agent: &str) -> String {
+        hex::encode(Sha256::digest(format!("{}|{}|{}", self.salt, ip, user_agent).as_bytes()))
+    }
+}
+
+impl ViewTracker {
+    /// The salted visitor hash for today, for callers that need to tell visitors
+    /// apart without a cookie.
+    pub fn fingerprint(&self, ip: &str, user_agent: &str) -> String {
+        let mut buf = self.buffer.lock().unwrap();
+        buf.roll_over();
+        buf.visitor_hash(ip, user_agent)
+    }
+
+    /// Counts one view and returns how many entries are waiting to be flushed.
+    pub fn record(&self, note_id: i32, ip: &str, user_agent: &str) -> usize {
+        let mut buf = self.buffer.lock().unwrap();
+
+        let day = buf.roll_over();
+        let hash = buf.visitor_hash(ip, user_agent);
+        let new_visitor = buf.seen.insert((note_id, hash.clone()));
+
+        let entry = buf.pending.entry((note_id, hash, day)).or_default();
+        entry.hits += 1;
+        entry.new_visitor |= new_visitor;
+        buf.pending.len()
+    }
+
+    fn take(&self) -> HashMap<(i32, String, NaiveDate), PendingView> {
+        std::mem::take(&mut self.buffer.lock().unwrap().pending)
+    }
+
+    // Puts entries back after a failed flush so they are retried next time
+    fn restore(&self, entries: HashMap<(i32, String, NaiveDate), PendingView>) {
+        let mut buf = self.buffer.lock().unwrap();
+        for (key, view) in entries {
+            let entry = buf.pending.entry(key).or_default();
+            entry.hits += view.hits;
+            entry.new_visitor |= view.new_visitor;
+        }
+    }
+}
+
+async fn write_batch<C: ConnectionTrait>(db: &C, entries: &HashMap<(i32, String, NaiveDate), PendingView>) -> Result<(), DbErr> {
+    let rows = entries.iter().map(|((note_id, hash, day), view)| note_view::ActiveModel {
+        note_id: Set(*note_id),
+        visitor_hash: Set(hash.clone()),
+        view_date: Set(*day),
+        hits: Set(view.hits),
+        ..Default::default()
+    });
+    note_view::Entity::insert_many(rows)
+        .on_conflict(
+            OnConflict::columns([note_view::Column::NoteId, note_view::Column::VisitorHash, note_view::Column::ViewDate])
+                .value(note_view::Column::Hits, Expr::cust("`hits` + VALUES(`hits`)"))
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+    let mut new_visitors: HashMap<i32, i32> = HashMap::new();
+    for ((note_id, _, _), view) in entries {
+        if view.new_visitor {
+            *new_visitors.entry(*note_id).or_default() += 1;
+        }
+    }
+    for (note_id, count) in new_visitors {
+        note::Entity::update_many()
+            .col_expr(note::Column::ViewCount, Expr::col(note::Column::ViewCount).add(count))
+            .filter(note::Column::Id.eq(note_id))
+            .exec(db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Writes the buffered views to `note_view` and bumps `note.view_count`.
+pub async fn flush(state: &AppState) -> Result<usize, DbErr> {
+    let entries = state.views.take();
+    if entries.is_empty() {
+        return Ok(0);
+    }
+
+    let count = entries.len();
+    let written = async {
+        let txn = state.db.begin().await?;
+        write_batch(&txn, &entries).await?;
+        txn.commit().await
+    }.await;
+
+    match written {
+        Ok(()) => Ok(count),
+        Err(e) => {
+            state.views.restore(entries);
+            Err(e)
+        }
+    }
+}
+
+/// Starts the background task that flushes buffered views.
+pub fn spawn(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = flush(&state).await {
+                tracing::error!("Flushing page views failed: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,206 @@
+use hmac::{Hmac, Mac};
+use sea_orm::{ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Set};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::entity::{webhook, webhook_delivery};
+use crate::events::DomainEvent;
+use crate::jobs;
+use crate::routes::{notes, web_info::load_site_meta, AppState};
+
+pub const NOTE_PUBLISHED: &str = "note.published";
+pub const NOTE_UPDATED: &str = "note.updated";
+pub const NOTE_DELETED: &str = "note.deleted";
+pub const TALK_CREATED: &str = "talk.created";
+pub const FRIEND_SUBMITTED: &str = "friend.submitted";
+pub const IMAGE_UPLOADED: &str = "image.uploaded";
+pub const EVENTS: &[&str] = &[NOTE_PUBLISHED, NOTE_UPDATED, NOTE_DELETED, TALK_CREATED, FRIEND_SUBMITTED, IMAGE_UPLOADED];
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_SUCCESS: &str = "success";
+pub const STATUS_FAILED: &str = "failed";
+
+pub const SIGNATURE_HEADER: &str = "x-saudade-signature";
+
+// One job per delivery; retries and backoff are the job queue's
+pub const JOB_DELIVER: &str = "webhook.deliver";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ERROR_CHARS: usize = 1000;
+
+/// HTTP client shared by deliveries.
+pub struct Webhooks {
+    client: reqwest::Client,
+}
+
+impl Default for Webhooks {
+    fn default() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent("saudade-blog-webhooks")
+            .build()
+            .unwrap_or_default();
+        Self { client }
+    }
+}
+
+/// Whether a webhook's `events` column includes `event`.
+pub fn subscribes(events: &str, event: &str) -> bool {
+    events.split(',').map(str::trim).any(|e| e == "*" || e == event)
+}
+
+/// `sha256=<hex>` HMAC of the exact request body.
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Logs one delivery per enabled webhook subscribed to `event` and queues a job
+/// to send each of them.
+pub async fn enqueue<C: ConnectionTrait>(db: &C, event: &str, data: Value) -> Result<usize, DbErr> {
+    let hooks: Vec<webhook::Model> = webhook::Entity::find()
+        .filter(webhook::Column::Enabled.eq(true))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter(|h| subscribes(&h.events, event))
+        .collect();
+    if hooks.is_empty() {
+        return Ok(0);
+    }
+
+    let site = load_site_meta(db).await;
+    let payload = json!({
+        "event": event,
+        "createdAt": chrono::Utc::now().to_rfc3339(),
+        "site": site.base_url,
+        "data": data,
+    }).to_string();
+
+    let now = chrono::Utc::now().naive_utc();
+    for h in &hooks {
+        let delivery = webhook_delivery::ActiveModel {
+            webhook_id: Set(h.id),
+            event: Set(event.to_string()),
+            payload: Set(payload.clone()),
+            status: Set(STATUS_PENDING.to_string()),
+            attempts: Set(0),
+            next_attempt_at: Set(now),
+            created_at: Set(now),
+            ..Default::default()
+        };
+        let id = webhook_delivery::Entity::insert(delivery).exec(db).await?.last_insert_id;
+        queue_delivery(db, id).await?;
+    }
+    Ok(hooks.len())
+}
+
+/// Queues the job that sends a logged delivery. Keyed by the delivery, so
+/// asking twice while it is waiting does not send it twice.
+pub async fn queue_delivery<C: ConnectionTrait>(db: &C, delivery_id: i32) -> Result<i32, DbErr> {
+    let key = format!("{}:{}", JOB_DELIVER, delivery_id);
+    jobs::enqueue(db, JOB_DELIVER, json!({ "deliveryKey": delivery_id }), Some(&key)).await
+}
+
+/// Fire-and-forget version of `enqueue`; the content change itself has already
+/// succeeded, so a failure here is only logged.
+pub async fn dispatch(state: &AppState, event: &str, data: Value) {
+    match enqueue(&state.db, event, data).await {
+        Ok(0) => {}
+        Ok(_) => jobs::wake(state),
+        Err(e) => tracing::warn!("Queueing webhook {} failed: {}", event, e),
+    }
+}
+
+/// Event bus subscriber turning domain events into webhook deliveries.
+pub async fn on_event(state: Arc<AppState>, event: Arc<DomainEvent>) {
+    match event.as_ref() {
+        DomainEvent::NoteCreated(n) | DomainEvent::NotePublished(n) if notes::is_visible(n) => {
+            dispatch(&state, NOTE_PUBLISHED, notes::webhook_data(n)).await;
+        }
+        DomainEvent::NoteUpdated { note, was_visible } => {
+            dispatch(&state, NOTE_UPDATED, notes::webhook_data(note)).await;
+            if !was_visible && notes::is_visible(note) {
+                dispatch(&state, NOTE_PUBLISHED, notes::webhook_data(note)).await;
+            }
+        }
+        DomainEvent::NoteDeleted(n) => dispatch(&state, NOTE_DELETED, notes::webhook_data(n)).await,
+        DomainEvent::TalkCreated { id } => {
+            dispatch(&state, TALK_CREATED, json!({ "talkKey": id, "path": "/talk" })).await;
+        }
+        DomainEvent::ImageUploaded { id, url } => {
+            dispatch(&state, IMAGE_UPLOADED, json!({ "imageKey": id, "imageUrl": url })).await;
+        }
+        DomainEvent::FriendSubmitted { id, name, url, .. } => {
+            dispatch(&state, FRIEND_SUBMITTED, json!({ "friendKey": id, "siteName": name, "siteUrl": url })).await;
+        }
+        _ => {}
+    }
+}
+
+async fn post(client: &reqwest::Client, hook: &webhook::Model, delivery: &webhook_delivery::Model) -> (Option<i32>, Result<(), String>) {
+    let response = client.post(&hook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("x-saudade-event", &delivery.event)
+        .header("x-saudade-delivery", delivery.id.to_string())
+        .header(SIGNATURE_HEADER, sign(&hook.secret, &delivery.payload))
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+
+    match response {
+        Ok(r) if r.status().is_success() => (Some(r.status().as_u16() as i32), Ok(())),
+        Ok(r) => {
+            let code = r.status().as_u16() as i32;
+            let body: String = r.text().await.unwrap_or_default().chars().take(MAX_ERROR_CHARS).collect();
+            (Some(code), Err(format!("HTTP {}: {}", code, body)))
+        }
+        Err(e) => (None, Err(e.to_string())),
+    }
+}
+
+/// Job handler for `JOB_DELIVER`: sends one delivery and records the attempt
+/// in its log row. An `Err` lets the job queue retry it; once the job is out of
+/// attempts the delivery is marked failed.
+pub async fn run_job(state: Arc<AppState>, payload: Value) -> Result<(), String> {
+    let id = payload["deliveryKey"].as_i64().ok_or("Missing deliveryKey")? as i32;
+    let found = webhook_delivery::Entity::find_by_id(id)
+        .find_also_related(webhook::Entity)
+        .one(&state.db)
+        .await
+        .map_err(|e| e.to_string())?;
+    // Gone with its webhook, or already sent
+    let Some((delivery, hook)) = found.filter(|(d, _)| d.status == STATUS_PENDING) else {
+        return Ok(());
+    };
+
+    let (code, result) = match hook {
+        Some(h) if h.enabled => post(&state.webhooks.client, &h, &delivery).await,
+        _ => (None, Err("Webhook disabled".to_string())),
+    };
+
+    let now = chrono::Utc::now().naive_utc();
+    let attempts = delivery.attempts + 1;
+    let mut active: webhook_delivery::ActiveModel = delivery.into();
+    active.attempts = Set(attempts);
+    active.response_code = Set(code);
+    match &result {
+        Ok(()) => {
+            active.status = Set(STATUS_SUCCESS.to_string());
+            active.last_error = Set(None);
+            active.delivered_at = Set(Some(now));
+        }
+        Err(e) => {
+            if attempts >= jobs::DEFAULT_MAX_ATTEMPTS {
+                active.status = Set(STATUS_FAILED.to_string());
+            } else {
+                active.next_attempt_at = Set(now + chrono::Duration::seconds(jobs::backoff_secs(attempts)));
+            }
+            active.last_error = Set(Some(e.clone()));
+        }
+    }
+    active.update(&state.db).await.map_err(|e| e.to_string())?;
+    result
+}
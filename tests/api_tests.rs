@@ -1,4 +1,4 @@
-use memory_blog_rust::{create_router, AppState};
+use saudade_blog::{create_router, AppState};
 use sea_orm::{DatabaseBackend, MockDatabase};
 use axum::{
     body::Body,
@@ -9,7 +9,7 @@ use tower::ServiceExt; // for `oneshot`
 #[tokio::test]
 async fn test_404_not_found() {
     let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
-    let app = create_router(AppState { db });
+    let app = create_router(AppState::new(db));
 
     let response = app
         .oneshot(Request::builder().uri("/api/wrong_path").body(Body::empty()).unwrap())
@@ -24,7 +24,7 @@ async fn test_public_notes_route_structure() {
     let db = MockDatabase::new(DatabaseBackend::MySql)
         .into_connection();
 
-    let app = create_router(AppState { db });
+    let app = create_router(AppState::new(db));
 
     let response = app
         .oneshot(Request::builder().uri("/api/public/notes").body(Body::empty()).unwrap())
@@ -33,3 +33,1562 @@ async fn test_public_notes_route_structure() {
 
     assert_ne!(response.status(), StatusCode::NOT_FOUND);
 }
+
+#[test]
+fn test_markdown_render_toc_and_sanitize() {
+    let md = "# Hello World\n\n## 安装 Rust\n\n## Hello World\n\n```rust\nfn main() {}\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\nNote[^1]\n\n[^1]: footnote\n\n<script>alert(1)</script>";
+    let rendered = saudade_blog::markdown::render(md);
+
+    let anchors: Vec<&str> = rendered.toc.iter().map(|t| t.anchor.as_str()).collect();
+    assert_eq!(anchors, vec!["hello-world", "安装-rust", "hello-world-1"]);
+    assert_eq!(rendered.toc[1].level, 2);
+
+    assert!(rendered.html.contains("<h2 id=\"安装-rust\">"));
+    assert!(rendered.html.contains("class=\"hl-"));
+    assert!(rendered.html.contains("<table>"));
+    assert!(rendered.html.contains("footnote-definition"));
+    assert!(!rendered.html.contains("<script"));
+}
+
+#[test]
+fn test_revision_line_diff() {
+    let lines = saudade_blog::routes::revisions::diff_lines("a\nb\nc\n", "a\nc\nd\n");
+    let summary: Vec<(&str, Option<usize>, Option<usize>, &str)> = lines
+        .iter()
+        .map(|l| (l.tag.as_str(), l.old_line, l.new_line, l.content.as_str()))
+        .collect();
+
+    assert_eq!(summary, vec![
+        ("equal", Some(1), Some(1), "a"),
+        ("delete", Some(2), None, "b"),
+        ("equal", Some(3), Some(2), "c"),
+        ("insert", None, Some(3), "d"),
+    ]);
+}
+
+#[tokio::test]
+async fn test_revision_routes_require_auth() {
+    let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+    let app = create_router(AppState::new(db));
+
+    let response = app
+        .oneshot(Request::builder().uri("/api/protected/notes/1/revisions").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_parse_publish_at_as_beijing_time() {
+    use saudade_blog::utils::{format_datetime, parse_datetime};
+
+    let dt = parse_datetime("2026-03-01 08:30:00").unwrap();
+    assert_eq!(dt.to_string(), "2026-03-01 00:30:00");
+    assert_eq!(format_datetime(dt), "2026-03-01 08:30:00");
+
+    let rfc = parse_datetime("2026-03-01T00:30:00Z").unwrap();
+    assert_eq!(rfc, dt);
+    assert!(parse_datetime("tomorrow").is_none());
+}
+
+#[tokio::test]
+async fn test_public_queries_exclude_scheduled_notes() {
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([Vec::<saudade_blog::entity::note::Model>::new()])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+
+    let response = create_router(state.clone())
+        .oneshot(Request::builder().uri("/api/public/topnotes").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    // Lists are validated by ETag only; a removed note would not move any updated_at
+    assert!(response.headers().get("last-modified").is_none());
+    assert!(response.headers().get("etag").is_some());
+
+    let state = std::sync::Arc::try_unwrap(state).ok().unwrap();
+    let log = format!("{:?}", state.db.into_transaction_log());
+    assert!(log.contains("NOT IN (?, ?)"));
+    assert!(log.contains("\"scheduled\""));
+}
+
+#[tokio::test]
+async fn test_note_detail_hides_unpublished_notes() {
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([Vec::<saudade_blog::entity::note::Model>::new()])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+
+    let response = create_router(state.clone())
+        .oneshot(Request::builder().uri("/api/public/notes/7").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert!(body["data"].is_null());
+
+    let state = std::sync::Arc::try_unwrap(state).ok().unwrap();
+    let log = format!("{:?}", state.db.into_transaction_log());
+    assert!(log.contains("`note`.`is_public` = ?"));
+    assert!(log.contains("\"scheduled\""));
+}
+
+#[test]
+fn test_slugify_transliterates_titles() {
+    use saudade_blog::slug::slugify;
+
+    assert_eq!(slugify("Hello, World!"), "hello-world");
+    assert_eq!(slugify("你好 Rust"), "ni-hao-rust");
+    assert_eq!(slugify("  --  "), "note");
+    assert!(slugify(&"a".repeat(200)).len() <= 80);
+}
+
+#[test]
+fn test_tag_filter_includes_child_tags() {
+    use saudade_blog::entity::note;
+    use saudade_blog::routes::tags::{parse_tag_refs, tag_filter, TagRef};
+    use sea_orm::{EntityTrait, QueryFilter, QueryTrait};
+
+    let refs = parse_tag_refs("1, 2:5,x,,3");
+    assert_eq!(refs.iter().map(|r| (r.id, r.level)).collect::<Vec<_>>(), vec![(1, None), (5, Some(2)), (3, None)]);
+
+    // tagIds must say which table an id belongs to
+    let refs: Vec<TagRef> = serde_json::from_str(r#"[{"id": 3, "level": 2}, "1:4"]"#).unwrap();
+    assert_eq!(refs, vec![TagRef { id: 3, level: Some(2) }, TagRef { id: 4, level: Some(1) }]);
+    assert!(serde_json::from_str::<Vec<TagRef>>("[3]").is_err());
+    assert!(serde_json::from_str::<Vec<TagRef>>(r#"["3:4"]"#).is_err());
+
+    let sql = note::Entity::find()
+        .filter(tag_filter(3, None))
+        .build(DatabaseBackend::MySql)
+        .to_string();
+    assert!(sql.contains("FROM `note_tag`"));
+    assert!(sql.contains("`tag_two`.`tag_one_id` = 3"));
+
+    let sql = note::Entity::find()
+        .filter(tag_filter(3, Some(2)))
+        .build(DatabaseBackend::MySql)
+        .to_string();
+    assert!(!sql.contains("tag_one_id"));
+}
+
+#[tokio::test]
+async fn test_legacy_note_tags_stay_numeric() {
+    use saudade_blog::entity::{tag_one, tag_two};
+    use saudade_blog::routes::tags::{set_note_tags, TagRef};
+    use sea_orm::MockExecResult;
+
+    // 7 exists in both tables, so only note_tag can say which one is meant
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![tag_one::Model { id: 7, name: "Rust".into(), level: Some(1), color: None }]])
+        .append_query_results([vec![tag_two::Model { id: 7, name: "Tokio".into(), level: Some(2), color: None, tag_one_id: Some(7) }]])
+        .append_exec_results([
+            MockExecResult { last_insert_id: 0, rows_affected: 0 },
+            MockExecResult { last_insert_id: 1, rows_affected: 1 },
+        ])
+        .into_connection();
+
+    let legacy = set_note_tags(&db, 1, &[TagRef { id: 7, level: Some(2) }]).await.unwrap();
+    assert_eq!(legacy, "7");
+    let insert = format!("{:?}", db.into_transaction_log().pop().unwrap());
+    assert!(insert.contains("INSERT INTO `note_tag`"));
+    assert!(insert.contains("Int(Some(7)), Int(Some(2))"));
+}
+
+#[tokio::test]
+async fn test_rss_feed_conditional_get() {
+    use saudade_blog::entity::{note, web_info};
+
+    let settings = vec![
+        web_info::Model { id: 1, key_name: "blog_title".into(), value: "Saudade & Co".into() },
+        web_info::Model { id: 2, key_name: "blogDomain".into(), value: "blog.example.com/".into() },
+    ];
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([settings.clone()])
+        .append_query_results([Vec::<note::Model>::new()])
+        .append_query_results([settings])
+        .append_query_results([Vec::<note::Model>::new()])
+        .into_connection();
+    let app = create_router(AppState::new(db));
+
+    let response = app.clone()
+        .oneshot(Request::builder().uri("/feed.xml").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers().get("etag").unwrap().clone();
+    // Renames and description edits move no updated_at, so only the ETag validates
+    assert!(!response.headers().contains_key("last-modified"));
+
+    let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains("<title>Saudade &amp; Co</title>"));
+    assert!(body.contains("https://blog.example.com/feed.xml"));
+
+    let response = app
+        .oneshot(Request::builder().uri("/feed.xml").header("if-none-match", etag).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[test]
+fn test_sitemap_rendering() {
+    use saudade_blog::routes::sitemap::{render_index, render_urlset, SitemapUrl};
+
+    let dt = chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(8, 0, 0).unwrap();
+    let urls = vec![
+        SitemapUrl { path: "/article/1".into(), lastmod: Some(dt) },
+        SitemapUrl { path: "/category/2".into(), lastmod: None },
+    ];
+
+    let xml = render_urlset("https://blog.example.com", &urls);
+    assert!(xml.contains("<loc>https://blog.example.com/article/1</loc><lastmod>2024-05-01T08:00:00Z</lastmod>"));
+    assert!(xml.contains("<loc>https://blog.example.com/category/2</loc></url>"));
+
+    let chunks: Vec<&[SitemapUrl]> = urls.chunks(1).collect();
+    let index = render_index("https://blog.example.com", &chunks);
+    assert!(index.contains("<sitemapindex"));
+    assert!(index.contains("<loc>https://blog.example.com/sitemaps/2.xml</loc>"));
+}
+
+#[tokio::test]
+async fn test_sitemap_ignores_host_header_without_domain() {
+    use saudade_blog::entity::web_info;
+
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([Vec::<web_info::Model>::new(), Vec::<web_info::Model>::new()])
+        .into_connection();
+    let app = create_router(AppState::new(db));
+
+    let response = app.clone()
+        .oneshot(Request::builder().uri("/sitemap.xml").header("host", "evil.example").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = app
+        .oneshot(Request::builder().uri("/robots.txt").header("host", "evil.example").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains("Disallow: /dashboard"));
+    assert!(!body.contains("Sitemap:"));
+}
+
+#[tokio::test]
+async fn test_prerender_requires_blog_domain() {
+    use saudade_blog::entity::web_info;
+
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([Vec::<web_info::Model>::new()])
+        .into_connection();
+    let response = create_router(AppState::new(db))
+        .oneshot(Request::builder().uri("/prerender/article/1").header("host", "evil.example").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(response.headers().get("cache-control").is_none());
+}
+
+#[test]
+fn test_crawler_detection() {
+    use saudade_blog::routes::prerender::is_crawler;
+
+    assert!(is_crawler("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)"));
+    assert!(is_crawler("facebookexternalhit/1.1"));
+    assert!(is_crawler("TelegramBot (like TwitterBot)"));
+    assert!(is_crawler("Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 Mobile/15E148 MicroMessenger/8.0.47"));
+    assert!(!is_crawler("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 Chrome/120.0 Safari/537.36"));
+}
+
+#[tokio::test]
+async fn test_archive_groups_by_year_and_month() {
+    use sea_orm::Value;
+    use std::collections::BTreeMap;
+
+    let row = |id: i32, y: i32, m: i32| -> BTreeMap<String, Value> {
+        let created = chrono::NaiveDate::from_ymd_opt(y, m as u32, 10).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        BTreeMap::from([
+            ("id".to_string(), Value::Int(Some(id))),
+            ("title".to_string(), Value::String(Some(Box::new(format!("Note {}", id))))),
+            ("slug".to_string(), Value::String(None)),
+            ("created_at".to_string(), Value::ChronoDateTime(Some(Box::new(created)))),
+            ("category_id".to_string(), Value::Int(Some(1))),
+            ("category_title".to_string(), Value::String(Some(Box::new("Rust".to_string())))),
+            ("year".to_string(), Value::Int(Some(y))),
+            ("month".to_string(), Value::Int(Some(m))),
+        ])
+    };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![row(3, 2024, 5), row(2, 2024, 5), row(1, 2023, 12)]])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+    let app = create_router(state.clone());
+
+    let response = app
+        .oneshot(Request::builder().uri("/api/public/archive").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let data = &json["data"];
+    assert_eq!(data["total"], 3);
+    assert_eq!(data["years"][0]["year"], 2024);
+    assert_eq!(data["years"][0]["months"][0]["count"], 2);
+    assert_eq!(data["years"][1]["months"][0]["month"], 12);
+    assert_eq!(data["years"][1]["months"][0]["notes"][0]["categoryTitle"], "Rust");
+
+    let log = std::sync::Arc::try_unwrap(state).ok().unwrap().db.into_transaction_log();
+    let sql = format!("{:?}", log[0]);
+    assert!(!sql.contains("`content`"));
+    assert!(sql.contains("LEFT JOIN `category`"));
+}
+
+#[test]
+fn test_stats_terms() {
+    use saudade_blog::text::terms;
+
+    let t = terms("Rust 异步编程: using Tokio with C++");
+    assert!(t.contains("rust"));
+    assert!(t.contains("tokio"));
+    assert!(t.contains("c++"));
+    assert!(t.contains("异步"));
+    assert!(t.contains("编程"));
+    assert!(!t.contains("using"));
+    assert!(!t.contains("with"));
+}
+
+#[tokio::test]
+async fn test_view_tracking_dedupes_and_flushes() {
+    use saudade_blog::views;
+    use sea_orm::MockExecResult;
+
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_exec_results([
+            MockExecResult { last_insert_id: 1, rows_affected: 2 },
+            MockExecResult { last_insert_id: 0, rows_affected: 1 },
+        ])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+
+    state.views.record(7, "10.0.0.1", "Firefox");
+    state.views.record(7, "10.0.0.1", "Firefox");
+    assert_eq!(state.views.record(7, "10.0.0.2", "Firefox"), 2);
+
+    assert_eq!(views::flush(&state).await.unwrap(), 2);
+    assert_eq!(views::flush(&state).await.unwrap(), 0);
+
+    let log = format!("{:?}", std::sync::Arc::try_unwrap(state).ok().unwrap().db.into_transaction_log());
+    assert!(log.contains("ON DUPLICATE KEY UPDATE"));
+    assert!(log.contains("`view_count` = `view_count` + ?"));
+    assert!(!log.contains("10.0.0.1"));
+}
+
+#[test]
+fn test_rate_limiter_window() {
+    use saudade_blog::rate_limit::RateLimiter;
+
+    let limiter = RateLimiter::new(2, std::time::Duration::from_secs(60));
+    assert!(limiter.check("1.2.3.4"));
+    assert!(limiter.check("1.2.3.4"));
+    assert!(!limiter.check("1.2.3.4"));
+    assert!(limiter.check("5.6.7.8"));
+}
+
+#[tokio::test]
+async fn test_reaction_rejects_unknown_emoji() {
+    let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+    let app = create_router(AppState::new(db));
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/public/reactions")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"targetType":"note","targetId":1,"emoji":"💩"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], 500);
+    assert_eq!(json["message"], "Unsupported emoji");
+}
+
+#[test]
+fn test_comment_markdown_is_strict() {
+    use saudade_blog::markdown::render_comment;
+
+    let html = render_comment("**hi** <script>alert(1)</script> [x](javascript:alert(1)) [ok](https://example.com)\n\n# Title\n\n![img](https://example.com/a.png)");
+    assert!(html.contains("<strong>hi</strong>"));
+    assert!(html.contains("&lt;script&gt;"));
+    assert!(!html.contains("javascript:"));
+    assert!(html.contains("rel=\"nofollow noopener noreferrer ugc\""));
+    assert!(!html.contains("<h1"));
+    assert!(!html.contains("<img"));
+}
+
+#[tokio::test]
+async fn test_public_comments_are_threaded() {
+    use saudade_blog::entity::comment;
+
+    let at = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let c = |id: i32, parent: Option<i32>| comment::Model {
+        id,
+        target_type: "note".into(),
+        target_id: 1,
+        parent_id: parent,
+        author_name: format!("guest{}", id),
+        author_email: "guest@example.com".into(),
+        author_website: None,
+        content: "hi".into(),
+        content_html: "<p>hi</p>".into(),
+        status: "approved".into(),
+        is_admin: false,
+        trained_as: None,
+        created_at: at,
+        updated_at: at,
+    };
+    // #4 replies to a comment that is not approved, so it must not show up
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![c(1, None), c(2, Some(1)), c(3, Some(2)), c(4, Some(9))]])
+        .into_connection();
+    let app = create_router(AppState::new(db));
+
+    let response = app
+        .oneshot(Request::builder().uri("/api/public/comments?targetType=note&targetId=1").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let threads = json["data"].as_array().unwrap();
+    assert_eq!(threads.len(), 1);
+    assert_eq!(threads[0]["replies"][0]["replies"][0]["commentKey"], 3);
+    assert!(!String::from_utf8_lossy(&body).contains("guest@example.com"));
+}
+
+#[test]
+fn test_spam_pipeline() {
+    use saudade_blog::spam::{SpamContext, SpamFilter, SpamInput, Verdict};
+
+    let filter = SpamFilter::default();
+    let now = chrono::Utc::now().timestamp();
+    let ctx = SpamContext { keywords: vec!["casino".into()], patterns: vec![], spam_probability: None };
+    let token = filter.issue_token_at(now - 30);
+    let input = |content: &'static str, honeypot: Option<&'static str>, form_token: Option<&str>| -> Verdict {
+        filter.judge(&SpamInput { author: "guest", email: "guest@example.com", website: None, content, honeypot, form_token }, &ctx)
+    };
+
+    assert_eq!(input("Nice post", None, Some(&token)), Verdict::Ham);
+    assert!(matches!(input("Nice post", Some("x"), Some(&token)), Verdict::Block(_)));
+    assert!(matches!(input("Nice post", None, Some(&filter.issue_token_at(now))), Verdict::Block(_)));
+    assert!(matches!(input("Nice post", None, None), Verdict::Suspicious(_)));
+    assert!(matches!(input("Nice post", None, Some(&SpamFilter::default().issue_token_at(now - 30))), Verdict::Suspicious(_)));
+    assert!(matches!(input("Best CASINO bonus", None, Some(&token)), Verdict::Block(_)));
+    assert!(matches!(input("https://a.io https://b.io https://c.io", None, Some(&token)), Verdict::Suspicious(_)));
+}
+
+#[tokio::test]
+async fn test_spam_patterns_keep_their_case() {
+    use saudade_blog::entity::{spam_token, web_info};
+    use saudade_blog::spam::{SpamFilter, SpamInput, Verdict};
+
+    let settings = vec![
+        web_info::Model { id: 1, key_name: "spamKeywords".into(), value: "Casino".into() },
+        // `\D` would turn into `\d` if the pattern were lowercased
+        web_info::Model { id: 2, key_name: "spamPatterns".into(), value: "buy\\D+now\n(".into() },
+    ];
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([settings.clone()])
+        .append_query_results([Vec::<spam_token::Model>::new()])
+        .append_query_results([settings])
+        .append_query_results([Vec::<spam_token::Model>::new()])
+        .into_connection();
+
+    let filter = SpamFilter::default();
+    let token = filter.issue_token_at(chrono::Utc::now().timestamp() - 30);
+    let input = |content| SpamInput { author: "guest", email: "guest@example.com", website: None, content, honeypot: None, form_token: Some(&token) };
+
+    let verdict = filter.evaluate(&db, &input("BUY it NOW")).await.unwrap();
+    assert_eq!(verdict, Verdict::Block("blocked pattern: buy\\D+now".to_string()));
+    let verdict = filter.evaluate(&db, &input("no CASINO here")).await.unwrap();
+    assert_eq!(verdict, Verdict::Block("blocked keyword: casino".to_string()));
+}
+
+#[test]
+fn test_captcha_proof_of_work() {
+    use saudade_blog::captcha::Captcha;
+
+    let captcha = Captcha::new(8);
+    let challenge = captcha.challenge();
+    let solution = Captcha::solve(&challenge.challenge, challenge.difficulty);
+    assert_eq!(captcha.verify(&solution), Ok(()));
+    assert_eq!(captcha.verify(&solution), Err("Captcha already used"));
+
+    // Lowering the difficulty in the challenge breaks its signature
+    let fresh = captcha.challenge().challenge;
+    let tampered = fresh.replacen(".8.", ".0.", 1);
+    assert!(captcha.verify(&format!("{}:0", tampered)).is_err());
+    assert!(Captcha::new(8).verify(&Captcha::solve(&fresh, 8)).is_err());
+    // A signature that is not even hex is refused, not compared
+    let (payload, _) = fresh.rsplit_once('.').unwrap();
+    assert_eq!(captcha.verify(&format!("{}.zz:0", payload)), Err("Invalid captcha"));
+}
+
+#[tokio::test]
+async fn test_login_requires_captcha_after_failures() {
+    use saudade_blog::entity::user;
+
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([Vec::<user::Model>::new(), vec![], vec![]])
+        .into_connection();
+    let app = create_router(AppState::new(db));
+    let login = || Request::builder()
+        .method("POST")
+        .uri("/api/login")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"username":"admin","password":"wrong"}"#))
+        .unwrap();
+
+    for _ in 0..3 {
+        let response = app.clone().oneshot(login()).await.unwrap();
+        let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], 500);
+    }
+
+    let response = app.oneshot(login()).await.unwrap();
+    let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], 428);
+    assert_eq!(json["message"], "Captcha required");
+}
+
+#[tokio::test]
+async fn test_mailer_delivers_to_mock_smtp_server() {
+    use saudade_blog::entity::web_info;
+    use saudade_blog::mailer::{self, Notification};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    // Minimal SMTP server that accepts one message and hands back the transcript
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        let mut transcript = String::new();
+        let mut in_data = false;
+        write.write_all(b"220 mock ESMTP\r\n").await.unwrap();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            transcript.push_str(&line);
+            transcript.push('\n');
+            let reply: &[u8] = if in_data {
+                if line != "." {
+                    continue;
+                }
+                in_data = false;
+                b"250 queued\r\n"
+            } else if line.starts_with("EHLO") {
+                b"250-mock\r\n250 AUTH PLAIN\r\n"
+            } else if line.starts_with("AUTH") {
+                b"235 ok\r\n"
+            } else if line == "DATA" {
+                in_data = true;
+                b"354 go ahead\r\n"
+            } else if line == "QUIT" {
+                write.write_all(b"221 bye\r\n").await.unwrap();
+                break;
+            } else {
+                b"250 ok\r\n"
+            };
+            write.write_all(reply).await.unwrap();
+        }
+        transcript
+    });
+
+    let setting = |id: i32, key: &str, value: &str| web_info::Model { id, key_name: key.into(), value: value.into() };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![
+            setting(1, "smtpHost", "127.0.0.1"),
+            setting(2, "smtpPort", &port.to_string()),
+            setting(3, "smtpSecurity", "none"),
+            setting(4, "smtpUsername", "blog"),
+            setting(5, "smtpPassword", "secret"),
+            setting(6, "smtpFrom", "blog@example.com"),
+            setting(7, "notifyEmail", "admin@example.com"),
+            setting(8, "blog_title", "Saudade"),
+        ]])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+    // The job payload round-trips through JSON like it does via the job table
+    let payload = serde_json::to_value(Notification::FriendSubmitted {
+        name: "Friend".into(),
+        url: "https://friend.example.com".into(),
+        description: "Hello".into(),
+    }).unwrap();
+
+    assert_eq!(mailer::run_job(state, payload).await, Ok(()));
+
+    let transcript = server.await.unwrap();
+    assert!(transcript.contains("MAIL FROM:<blog@example.com>"));
+    assert!(transcript.contains("RCPT TO:<admin@example.com>"));
+    assert!(transcript.contains("To: admin@example.com"));
+    assert!(transcript.contains("Subject: =?UTF-8?B?"));
+}
+
+#[tokio::test]
+async fn test_webhook_enqueue_and_signature() {
+    use saudade_blog::entity::{job, web_info, webhook};
+    use saudade_blog::{jobs, webhooks};
+    use sea_orm::MockExecResult;
+
+    assert_eq!(
+        webhooks::sign("key", "The quick brown fox jumps over the lazy dog"),
+        "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+    );
+    assert!(webhooks::subscribes("note.published, talk.created", "talk.created"));
+    assert!(webhooks::subscribes("*", "image.uploaded"));
+    assert!(!webhooks::subscribes("note.updated", "note.deleted"));
+
+    let at = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let hook = |id: i32, events: &str| webhook::Model {
+        id,
+        name: format!("hook{}", id),
+        url: "https://example.com/hook".into(),
+        secret: "s".into(),
+        events: events.into(),
+        enabled: true,
+        created_at: at,
+        updated_at: at,
+    };
+    let job = |id: i32, delivery: i32| job::Model {
+        id,
+        job_type: webhooks::JOB_DELIVER.into(),
+        payload: format!(r#"{{"deliveryKey":{}}}"#, delivery),
+        status: jobs::STATUS_QUEUED.into(),
+        attempts: 0,
+        max_attempts: jobs::DEFAULT_MAX_ATTEMPTS,
+        unique_key: Some(format!("webhook.deliver:{}", delivery)),
+        last_error: None,
+        run_at: at,
+        locked_by: None,
+        locked_at: None,
+        finished_at: None,
+        created_at: at,
+        updated_at: at,
+    };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![hook(1, "note.deleted"), hook(2, "*"), hook(3, "note.published,note.updated")]])
+        .append_query_results([vec![web_info::Model { id: 1, key_name: "blogDomain".into(), value: "blog.example.com".into() }]])
+        // Per webhook: the delivery row, then its job (uniqueness check, insert, read back)
+        .append_query_results([Vec::<job::Model>::new()])
+        .append_query_results([vec![job(1, 11)]])
+        .append_query_results([Vec::<job::Model>::new()])
+        .append_query_results([vec![job(2, 12)]])
+        .append_exec_results([
+            MockExecResult { last_insert_id: 11, rows_affected: 1 },
+            MockExecResult { last_insert_id: 1, rows_affected: 1 },
+            MockExecResult { last_insert_id: 12, rows_affected: 1 },
+            MockExecResult { last_insert_id: 2, rows_affected: 1 },
+        ])
+        .into_connection();
+
+    let queued = webhooks::enqueue(&db, webhooks::NOTE_PUBLISHED, serde_json::json!({ "noteKey": 7 })).await.unwrap();
+    assert_eq!(queued, 2);
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert_eq!(log.matches("INSERT INTO `webhook_delivery`").count(), 2);
+    assert!(log.contains(r#"\"site\":\"https://blog.example.com\""#));
+    assert_eq!(log.matches("INSERT INTO `job`").count(), 2);
+    assert!(log.contains(r#"String(Some("webhook.deliver:12"))"#));
+}
+
+#[tokio::test]
+async fn test_event_bus_delivers_in_order() {
+    use saudade_blog::events::{self, DomainEvent};
+    use std::sync::Arc;
+
+    let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+    let state = Arc::new(AppState::new(db));
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+    // A failing subscriber must not keep the others from running
+    state.events.subscribe("broken", |_, _| async { panic!("subscriber failure") });
+    state.events.subscribe("recorder", move |_, event: Arc<DomainEvent>| {
+        let tx = tx.clone();
+        async move {
+            if let DomainEvent::TalkCreated { id } = event.as_ref() {
+                tx.send(*id).unwrap();
+            }
+        }
+    });
+    events::spawn(state.clone());
+
+    state.events.publish(DomainEvent::TalkCreated { id: 1 });
+    state.events.publish(DomainEvent::TalkDeleted { id: 1 });
+    state.events.publish(DomainEvent::TalkCreated { id: 2 });
+
+    assert_eq!(rx.recv().await, Some(1));
+    assert_eq!(rx.recv().await, Some(2));
+}
+
+#[tokio::test]
+async fn test_job_queue_dedupes_and_backs_off() {
+    use saudade_blog::entity::job;
+    use saudade_blog::jobs;
+
+    assert_eq!(jobs::backoff_secs(1), 30);
+    assert_eq!(jobs::backoff_secs(2), 120);
+    assert_eq!(jobs::backoff_secs(3), 480);
+    assert_eq!(jobs::backoff_secs(9), 6 * 3600);
+
+    let now = chrono::Utc::now().naive_utc();
+    let pending = job::Model {
+        id: 7,
+        job_type: "email.send".into(),
+        payload: "{}".into(),
+        status: jobs::STATUS_QUEUED.into(),
+        attempts: 0,
+        max_attempts: jobs::DEFAULT_MAX_ATTEMPTS,
+        unique_key: Some("digest:2026-10-19".into()),
+        last_error: None,
+        run_at: now,
+        locked_by: None,
+        locked_at: None,
+        finished_at: None,
+        created_at: now,
+        updated_at: now,
+    };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![pending]])
+        .into_connection();
+
+    // Same key while the first job is still pending: no second insert
+    let id = jobs::enqueue(&db, "email.send", serde_json::json!({}), Some("digest:2026-10-19")).await.unwrap();
+    assert_eq!(id, 7);
+    let log = db.into_transaction_log();
+    assert_eq!(log.len(), 1);
+    assert!(format!("{:?}", log[0]).contains("SELECT"));
+}
+
+#[tokio::test]
+async fn test_public_responses_cached_until_admin_write() {
+    use saudade_blog::entity::tag_one;
+    use sea_orm::MockExecResult;
+
+    let tag = |id: i32, name: &str| tag_one::Model { id, name: name.into(), level: Some(1), color: None };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![tag(1, "Rust")], vec![tag(2, "Go")]])
+        .append_exec_results([MockExecResult { last_insert_id: 2, rows_affected: 1 }])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+    let app = create_router(state.clone());
+
+    let fetch = |app: axum::Router| async move {
+        let response = app
+            .oneshot(Request::builder().uri("/api/public/tagone").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    };
+
+    assert!(fetch(app.clone()).await.contains("Rust"));
+    // The mock has moved on to "Go"; the second read must not reach the database
+    assert!(fetch(app.clone()).await.contains("Rust"));
+    assert_eq!(state.response_cache.len(), 1);
+
+    let response = app.clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/protected/tagone")
+                .header("authorization", "mock-token-for-admin")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"title":"Go","color":""}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(state.response_cache.is_empty());
+    assert!(fetch(app).await.contains("Go"));
+}
+
+#[tokio::test]
+async fn test_json_etag_and_compression() {
+    use saudade_blog::entity::tag_one;
+
+    let tags: Vec<tag_one::Model> = (1..=20)
+        .map(|id| tag_one::Model { id, name: format!("Tag {}", id), level: Some(1), color: Some("#ffffff".into()) })
+        .collect();
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([tags])
+        .into_connection();
+    let app = create_router(AppState::new(db));
+
+    let response = app.clone()
+        .oneshot(Request::builder().uri("/api/public/tagone").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers().get("etag").unwrap().clone();
+    assert!(etag.to_str().unwrap().starts_with("W/\""));
+    assert_eq!(response.headers().get("cache-control").unwrap(), "no-cache");
+
+    let response = app.clone()
+        .oneshot(Request::builder().uri("/api/public/tagone").header("if-none-match", etag.clone()).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers().get("etag").unwrap(), &etag);
+
+    let response = app
+        .oneshot(Request::builder().uri("/api/public/tagone").header("accept-encoding", "gzip").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.headers().get("content-encoding").unwrap(), "gzip");
+    let compressed = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+    assert_eq!(&compressed[..2], &[0x1f, 0x8b]);
+}
+
+#[tokio::test]
+async fn test_category_counts_use_one_grouped_query() {
+    use saudade_blog::entity::category;
+    use sea_orm::Value;
+    use std::collections::BTreeMap;
+
+    let cat = |id: i32, name: &str| category::Model {
+        id, name: name.into(), introduce: None, path_name: None, icon: None, color: None, parent_id: None, sort_order: 0,
+    };
+    let at = |d: u32| chrono::NaiveDate::from_ymd_opt(2026, 5, d).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let stats = BTreeMap::from([
+        ("category_id".to_string(), Value::Int(Some(1))),
+        ("total".to_string(), Value::BigInt(Some(5))),
+        ("public".to_string(), Value::BigInt(Some(3))),
+        ("latest".to_string(), Value::ChronoDateTime(Some(Box::new(at(20))))),
+        ("latest_public".to_string(), Value::ChronoDateTime(Some(Box::new(at(10))))),
+    ]);
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![cat(1, "Rust"), cat(2, "Empty")]])
+        .append_query_results([vec![stats.clone()]])
+        .append_query_results([vec![cat(1, "Rust"), cat(2, "Empty")]])
+        .append_query_results([vec![stats]])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+    let app = create_router(state.clone());
+
+    let get = |app: axum::Router, uri: &'static str| async move {
+        let response = app
+            .oneshot(Request::builder().uri(uri).header("authorization", "mock-token-for-admin").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+    };
+
+    let public = get(app.clone(), "/api/public/category").await;
+    assert_eq!(public["data"][0]["noteCount"], 3);
+    assert_eq!(public["data"][0]["latestNoteTime"], "2026-05-10 08:00:00");
+    assert!(public["data"][0].get("publicCount").is_none());
+    assert_eq!(public["data"][1]["noteCount"], 0);
+    assert!(public["data"][1]["latestNoteTime"].is_null());
+
+    let admin = get(app, "/api/protected/category").await;
+    assert_eq!(admin["data"][0]["noteCount"], 5);
+    assert_eq!(admin["data"][0]["publicCount"], 3);
+    assert_eq!(admin["data"][0]["latestNoteTime"], "2026-05-20 08:00:00");
+
+    let db = std::sync::Arc::try_unwrap(state).ok().unwrap().db;
+    let log = db.into_transaction_log();
+    // Two statements per listing no matter how many categories there are
+    assert_eq!(log.len(), 4);
+    let stats_sql = format!("{:?}", log[1]);
+    assert!(stats_sql.contains("GROUP BY"));
+    assert!(stats_sql.contains("CASE WHEN"));
+}
+
+#[tokio::test]
+async fn test_category_tree_and_cycle_prevention() {
+    use saudade_blog::entity::category;
+    use saudade_blog::routes::categories::creates_cycle;
+    use sea_orm::Value;
+    use std::collections::BTreeMap;
+
+    let cat = |id: i32, name: &str, parent_id: Option<i32>, sort_order: i32| category::Model {
+        id, name: name.into(), introduce: None, path_name: None, icon: None, color: None, parent_id, sort_order,
+    };
+    // Already in display order, as the sort_order query returns them
+    let cats = vec![
+        cat(4, "Life", None, 0),
+        cat(1, "Tech", None, 1),
+        cat(3, "Async", Some(2), 0),
+        cat(2, "Rust", Some(1), 0),
+    ];
+
+    assert!(creates_cycle(&cats, 1, 1));
+    assert!(creates_cycle(&cats, 1, 3));
+    assert!(!creates_cycle(&cats, 3, 4));
+    assert!(!creates_cycle(&cats, 2, 1));
+
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([cats.clone()])
+        .append_query_results([Vec::<BTreeMap<String, Value>>::new()])
+        .append_query_results([cats])
+        .into_connection();
+    let app = create_router(AppState::new(db));
+
+    let response = app.clone()
+        .oneshot(Request::builder().uri("/api/public/category/tree").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let tree: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(tree["data"][0]["categoryTitle"], "Life");
+    assert_eq!(tree["data"][1]["categoryTitle"], "Tech");
+    assert_eq!(tree["data"][1]["children"][0]["categoryTitle"], "Rust");
+    assert_eq!(tree["data"][1]["children"][0]["children"][0]["categoryKey"], 3);
+    assert_eq!(tree["data"].as_array().unwrap().len(), 2);
+
+    // Moving "Tech" under its grandchild is refused before anything is written
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/protected/category/1")
+                .header("authorization", "mock-token-for-admin")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"parentKey":3}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["code"], 500);
+    assert!(body["message"].as_str().unwrap().contains("descendants"));
+}
+
+#[test]
+fn test_client_ip_only_trusts_local_proxy() {
+    use axum::http::HeaderMap;
+    use saudade_blog::views::client_ip;
+
+    let mut headers = HeaderMap::new();
+    headers.insert("x-forwarded-for", "1.2.3.4, 203.0.113.9".parse().unwrap());
+    let nginx: std::net::SocketAddr = "127.0.0.1:50000".parse().unwrap();
+    let direct: std::net::SocketAddr = "198.51.100.7:50000".parse().unwrap();
+
+    // Behind nginx only the hop nginx appended counts, not what the client wrote
+    assert_eq!(client_ip(&headers, Some(nginx)), "203.0.113.9");
+    headers.insert("x-real-ip", "203.0.113.10".parse().unwrap());
+    assert_eq!(client_ip(&headers, Some(nginx)), "203.0.113.10");
+    // Straight from the internet the headers are ignored
+    assert_eq!(client_ip(&headers, Some(direct)), "198.51.100.7");
+    assert_eq!(client_ip(&HeaderMap::new(), None), "unknown");
+}
+
+#[test]
+fn test_visitor_fingerprint_is_stable_within_a_day() {
+    use saudade_blog::views::ViewTracker;
+
+    let tracker = ViewTracker::default();
+    let first = tracker.fingerprint("203.0.113.9", "Firefox");
+    assert_eq!(tracker.fingerprint("203.0.113.9", "Firefox"), first);
+    assert_ne!(tracker.fingerprint("203.0.113.9", "Chrome"), first);
+    // The salt is per process, so another tracker cannot link the same visitor
+    assert_ne!(ViewTracker::default().fingerprint("203.0.113.9", "Firefox"), first);
+    assert_eq!(tracker.record(1, "203.0.113.9", "Firefox"), 1);
+}
+
+fn note_model(id: i32, title: &str, is_public: bool) -> saudade_blog::entity::note::Model {
+    let at = chrono::NaiveDate::from_ymd_opt(2024, 3, id as u32).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    saudade_blog::entity::note::Model {
+        id,
+        title: title.into(),
+        content: "body".into(),
+        description: None,
+        cover: None,
+        is_top: None,
+        status: Some(if is_public { "published" } else { "draft" }.into()),
+        created_at: at,
+        updated_at: at,
+        is_public,
+        tags: None,
+        category_id: None,
+        publish_at: None,
+        slug: None,
+        view_count: 0,
+    }
+}
+
+#[tokio::test]
+async fn test_series_members_keep_their_order() {
+    use saudade_blog::entity::series;
+    use sea_orm::MockExecResult;
+
+    let at = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let s = series::Model { id: 9, title: "Async Rust".into(), description: None, cover: None, created_at: at, updated_at: at };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![s.clone()], vec![s]])
+        .append_exec_results([
+            MockExecResult { last_insert_id: 0, rows_affected: 1 },
+            MockExecResult { last_insert_id: 0, rows_affected: 2 },
+            MockExecResult { last_insert_id: 1, rows_affected: 3 },
+        ])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+
+    let response = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/protected/series/9")
+                .header("authorization", "mock-token-for-admin")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"noteKeys":[5,3,5,8]}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["code"], 200);
+
+    let log = std::sync::Arc::try_unwrap(state).ok().unwrap().db.into_transaction_log();
+    let delete = format!("{:?}", log[log.len() - 2]);
+    assert!(delete.contains("DELETE FROM `series_note`"));
+    assert!(delete.contains("`note_id` IN (?, ?, ?, ?)"));
+    // Duplicates collapse to their first place and positions follow the request order
+    let insert = format!("{:?}", log[log.len() - 1]);
+    assert!(insert.contains("INSERT INTO `series_note`"));
+    assert!(insert.contains(
+        "[Int(Some(9)), Int(Some(5)), Int(Some(0)), Int(Some(9)), Int(Some(3)), Int(Some(1)), Int(Some(9)), Int(Some(8)), Int(Some(2))]"
+    ));
+}
+
+#[tokio::test]
+async fn test_note_detail_series_context() {
+    use saudade_blog::entity::{category, note, series, series_note};
+
+    let at = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let s = series::Model { id: 9, title: "Async Rust".into(), description: None, cover: None, created_at: at, updated_at: at };
+    let member = |id: i32, note_id: i32, position: i32| series_note::Model { id, series_id: 9, note_id, position };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![(note_model(3, "Pinning", true), None::<category::Model>)]])
+        // previous and next note by date, then the note's own tags for related notes
+        .append_query_results([Vec::<note::Model>::new(), Vec::<note::Model>::new()])
+        .append_query_results([Vec::<saudade_blog::entity::note_tag::Model>::new()])
+        .append_query_results([vec![(member(2, 3, 1), Some(s))]])
+        .append_query_results([vec![
+            (member(1, 1, 0), Some(note_model(1, "Futures", true))),
+            (member(2, 3, 1), Some(note_model(3, "Pinning", true))),
+            (member(3, 4, 2), Some(note_model(4, "Streams", true))),
+        ]])
+        .into_connection();
+    let app = create_router(AppState::new(db));
+
+    let response = app
+        .oneshot(Request::builder().uri("/api/public/notes/3").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    // Series, reactions and neighbours change without touching updated_at
+    assert!(response.headers().get("last-modified").is_none());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    let context = &body["data"]["series"];
+    assert_eq!(context["seriesTitle"], "Async Rust");
+    assert_eq!(context["part"], 2);
+    assert_eq!(context["total"], 3);
+    assert_eq!(context["prev"]["noteTitle"], "Futures");
+    assert_eq!(context["prev"]["part"], 1);
+    assert_eq!(context["next"]["noteTitle"], "Streams");
+    assert_eq!(context["next"]["part"], 3);
+}
+
+#[tokio::test]
+async fn test_public_series_hides_unpublished_parts() {
+    use saudade_blog::entity::{series, series_note};
+
+    let at = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let s = series::Model { id: 9, title: "Async Rust".into(), description: None, cover: None, created_at: at, updated_at: at };
+    let member = |id: i32, note_id: i32, position: i32| series_note::Model { id, series_id: 9, note_id, position };
+    // The draft between these two is filtered out by the query, so the rest are renumbered
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![s]])
+        .append_query_results([vec![
+            (member(1, 1, 0), Some(note_model(1, "Futures", true))),
+            (member(3, 4, 2), Some(note_model(4, "Streams", true))),
+        ]])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+
+    let response = create_router(state.clone())
+        .oneshot(Request::builder().uri("/api/public/series/9").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    let parts = body["data"]["parts"].as_array().unwrap();
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[1]["noteKey"], 4);
+    assert_eq!(parts[1]["part"], 2);
+
+    let log = std::sync::Arc::try_unwrap(state).ok().unwrap().db.into_transaction_log();
+    let parts_sql = format!("{:?}", log[1]);
+    assert!(parts_sql.contains("`note`.`is_public` = ?"));
+    assert!(parts_sql.contains("\"scheduled\""));
+    assert!(parts_sql.contains("ORDER BY `series_note`.`position` ASC"));
+}
+
+#[tokio::test]
+async fn test_related_notes_score_tags_over_category() {
+    use saudade_blog::entity::{note, note_tag};
+    use saudade_blog::routes::related::related_notes;
+    use sea_orm::Value;
+    use std::collections::BTreeMap;
+
+    let n = note::Model { category_id: Some(2), ..note_model(1, "Pinning", true) };
+    let tag = |id: i32, level: i32, tag_id: i32| note_tag::Model { id, note_id: 1, tag_id, level };
+    let link = |id: i32, score: i64| BTreeMap::from([
+        ("id".to_string(), Value::Int(Some(id))),
+        ("title".to_string(), Value::String(Some(Box::new(format!("Note {}", id))))),
+        ("slug".to_string(), Value::String(None)),
+        ("description".to_string(), Value::String(None)),
+        ("cover".to_string(), Value::String(None)),
+        ("created_at".to_string(), Value::ChronoDateTime(Some(Box::new(n.created_at)))),
+        ("score".to_string(), Value::BigInt(Some(score))),
+    ]);
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![tag(1, 1, 3), tag(2, 2, 7)]])
+        .append_query_results([vec![link(10, 5), link(11, 2), link(12, 1)]])
+        .into_connection();
+
+    let related = related_notes(&db, &n).await.unwrap();
+    let ranked: Vec<(i32, Option<i64>)> = related.iter().map(|r| (r.id, r.score)).collect();
+    assert_eq!(ranked, vec![(10, Some(5)), (11, Some(2)), (12, Some(1))]);
+
+    // Shared tags count twice as much as the category, ranked and capped by the database
+    let log = db.into_transaction_log();
+    assert_eq!(log.len(), 2);
+    let sql = format!("{:?}", log[1]);
+    assert!(sql.contains("(SELECT COUNT(`note_tag`.`id`) FROM `note_tag` WHERE `note_tag`.`note_id` = `note`.`id`"));
+    assert!(sql.contains("CASE WHEN (`note`.`category_id` = ?) THEN ? ELSE ? END"));
+    assert!(sql.contains("ORDER BY `score` DESC, `note`.`created_at` DESC LIMIT ?"));
+    assert!(sql.contains("`note`.`is_public` = ?"));
+    assert!(!sql.contains("`note`.`content`"));
+}
+
+#[tokio::test]
+async fn test_related_notes_fall_back_to_category() {
+    use saudade_blog::entity::{note, note_tag};
+    use saudade_blog::routes::related::related_notes;
+
+    let n = note::Model { category_id: Some(2), ..note_model(1, "Untagged", true) };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([Vec::<note_tag::Model>::new()])
+        .append_query_results([Vec::<note::Model>::new()])
+        .into_connection();
+
+    related_notes(&db, &n).await.unwrap();
+    let sql = format!("{:?}", db.into_transaction_log()[1]);
+    assert!(sql.contains("`note`.`category_id` = ?"));
+    assert!(!sql.contains("FROM `note_tag`"));
+
+    // Without tags or a category there is nothing to compare against
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([Vec::<note_tag::Model>::new()])
+        .into_connection();
+    assert!(related_notes(&db, &note_model(1, "Alone", true)).await.unwrap().is_empty());
+    assert_eq!(db.into_transaction_log().len(), 1);
+}
+
+#[tokio::test]
+async fn test_neighbours_are_ordered_by_date() {
+    use saudade_blog::entity::note;
+    use saudade_blog::routes::related::neighbours;
+
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![note_model(2, "Older", true)], vec![note_model(4, "Newer", true)]])
+        .into_connection();
+
+    let (prev, next) = neighbours(&db, &note_model(3, "Current", true)).await.unwrap();
+    assert_eq!(prev.map(|p| p.title), Some("Older".to_string()));
+    assert_eq!(next.map(|n| n.title), Some("Newer".to_string()));
+
+    let log = db.into_transaction_log();
+    let prev_sql = format!("{:?}", log[0]);
+    let next_sql = format!("{:?}", log[1]);
+    assert!(prev_sql.contains("`note`.`created_at` < ?"));
+    assert!(prev_sql.contains("ORDER BY `note`.`created_at` DESC"));
+    assert!(next_sql.contains("`note`.`created_at` > ?"));
+    assert!(next_sql.contains("ORDER BY `note`.`created_at` ASC"));
+
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([Vec::<note::Model>::new(), Vec::<note::Model>::new()])
+        .into_connection();
+    let (prev, next) = neighbours(&db, &note_model(3, "Only", true)).await.unwrap();
+    assert!(prev.is_none() && next.is_none());
+}
+
+#[tokio::test]
+async fn test_scheduled_note_gets_a_publish_job() {
+    use saudade_blog::entity::{job, note};
+    use saudade_blog::{jobs, scheduler};
+    use sea_orm::MockExecResult;
+
+    let at = chrono::NaiveDate::from_ymd_opt(2030, 5, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let n = note::Model { status: Some("scheduled".into()), is_public: false, publish_at: Some(at), ..note_model(7, "Later", false) };
+    let queued = job::Model {
+        id: 3,
+        job_type: scheduler::JOB_PUBLISH.into(),
+        payload: r#"{"noteKey":7}"#.into(),
+        status: jobs::STATUS_QUEUED.into(),
+        attempts: 0,
+        max_attempts: jobs::DEFAULT_MAX_ATTEMPTS,
+        unique_key: Some("note.publish:7".into()),
+        last_error: None,
+        run_at: at,
+        locked_by: None,
+        locked_at: None,
+        finished_at: None,
+        created_at: at,
+        updated_at: at,
+    };
+
+    // First save: a job due at publishAt
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([Vec::<job::Model>::new(), vec![queued.clone()]])
+        .append_exec_results([MockExecResult { last_insert_id: 3, rows_affected: 1 }])
+        .into_connection();
+    scheduler::schedule(&db, &n).await.unwrap();
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("INSERT INTO `job`"));
+    assert!(log.contains(r#"String(Some("note.publish:7"))"#));
+    assert!(log.contains("ChronoDateTime(Some(2030-05-01T12:00:00))"));
+
+    // Moving publishAt moves the queued job instead of adding another
+    let later = at + chrono::Duration::days(1);
+    let moved = note::Model { publish_at: Some(later), ..n.clone() };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![queued.clone()], vec![job::Model { run_at: later, ..queued.clone() }]])
+        .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 1 }])
+        .into_connection();
+    scheduler::schedule(&db, &moved).await.unwrap();
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(!log.contains("INSERT INTO `job`"));
+    assert!(log.contains("UPDATE `job` SET"));
+    assert!(log.contains("ChronoDateTime(Some(2030-05-02T12:00:00))"));
+
+    // Moved while the old job is running: that job lets go of the key and a new one takes it
+    let running = job::Model { status: jobs::STATUS_RUNNING.into(), ..queued.clone() };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![running], vec![job::Model { id: 4, run_at: later, ..queued }]])
+        .append_exec_results([
+            MockExecResult { last_insert_id: 0, rows_affected: 1 },
+            MockExecResult { last_insert_id: 4, rows_affected: 1 },
+        ])
+        .into_connection();
+    scheduler::schedule(&db, &moved).await.unwrap();
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("UPDATE `job` SET `unique_key` = ? WHERE `job`.`id` = ?"));
+    assert!(log.contains("INSERT INTO `job`"));
+    assert!(log.contains("ChronoDateTime(Some(2030-05-02T12:00:00))"));
+
+    // Drafts get no job
+    let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+    scheduler::schedule(&db, &note::Model { status: Some("draft".into()), ..n }).await.unwrap();
+    assert!(db.into_transaction_log().is_empty());
+}
+
+#[tokio::test]
+async fn test_reaction_drops_cached_lists_of_its_target() {
+    use saudade_blog::entity::{reaction, tag_one, talk};
+    use sea_orm::{MockExecResult, Value};
+    use std::collections::BTreeMap;
+
+    let at = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let t = talk::Model { id: 1, title: None, content: "hi".into(), created_at: at, updated_at: at };
+    let r = reaction::Model { id: 1, target_type: "talk".into(), target_id: 1, emoji: "👍".into(), visitor_hash: "h".into(), created_at: at };
+    // Tuple rows are read by position, and mock rows keep their columns sorted by name
+    let count = BTreeMap::from([
+        ("0_target_id".to_string(), Value::Int(Some(1))),
+        ("1_emoji".to_string(), Value::String(Some(Box::new("👍".to_string())))),
+        ("2_count".to_string(), Value::BigInt(Some(1))),
+    ]);
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([Vec::<talk::Model>::new()])
+        .append_query_results([vec![tag_one::Model { id: 1, name: "Rust".into(), level: Some(1), color: None }]])
+        // The toggle: target lookup, existing reaction, insert, then the fresh summary
+        .append_query_results([vec![t]])
+        .append_query_results([Vec::<reaction::Model>::new(), vec![r]])
+        .append_query_results([vec![count]])
+        .append_query_results([vec![BTreeMap::from([("emoji".to_string(), Value::String(Some(Box::new("👍".to_string()))))])]])
+        .append_exec_results([MockExecResult { last_insert_id: 1, rows_affected: 1 }])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+    let app = create_router(state.clone());
+
+    for uri in ["/api/public/talk", "/api/public/tagone"] {
+        app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+    }
+    assert_eq!(state.response_cache.len(), 2);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/public/reactions")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"targetType":"talk","targetId":1,"emoji":"👍"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    // The cookie is a random id, not the 64-char view fingerprint
+    let cookie = response.headers().get("set-cookie").unwrap().to_str().unwrap().to_string();
+    let id = cookie.trim_start_matches("saudade_vid=").split(';').next().unwrap();
+    assert_eq!(id.len(), 32);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["data"]["counts"]["👍"], 1);
+
+    // The talk list is stale now; the tag list is not
+    assert_eq!(state.response_cache.len(), 1);
+}
+
+#[tokio::test]
+async fn test_tag_feed_title_and_missing_domain() {
+    use saudade_blog::entity::{category, tag_one, tag_two, web_info};
+
+    let settings = vec![
+        web_info::Model { id: 1, key_name: "blog_title".into(), value: "Saudade".into() },
+        web_info::Model { id: 2, key_name: "author".into(), value: "Lin".into() },
+        web_info::Model { id: 3, key_name: "blogDomain".into(), value: "blog.example.com".into() },
+    ];
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([settings])
+        .append_query_results([vec![tag_one::Model { id: 3, name: "Rust".into(), level: Some(1), color: None }]])
+        .append_query_results([vec![(note_model(1, "Pinning", true), None::<category::Model>)]])
+        .append_query_results([Vec::<saudade_blog::entity::note_tag::Model>::new()])
+        .append_query_results([Vec::<tag_one::Model>::new()])
+        .append_query_results([Vec::<(tag_two::Model, Option<tag_one::Model>)>::new()])
+        .append_query_results([vec![web_info::Model { id: 1, key_name: "blog_title".into(), value: "Saudade".into() }]])
+        .into_connection();
+    let app = create_router(AppState::new(db));
+
+    let response = app.clone()
+        .oneshot(Request::builder().uri("/feed.xml?tag=3&tag_level=1").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let body = http_body_util::BodyExt::collect(response.into_body()).await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains("<title>Saudade - #Rust</title>"));
+    assert!(body.contains("<dc:creator>Lin</dc:creator>"));
+    assert!(!body.contains("<author>"));
+    assert!(body.contains("<link>https://blog.example.com/article/1</link>"));
+
+    // Without a domain every link would be relative, so there is no feed
+    let response = app
+        .oneshot(Request::builder().uri("/atom.xml").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_feed_etag_follows_category_rename() {
+    use saudade_blog::entity::{category, tag_one, tag_two, web_info};
+
+    let settings = vec![web_info::Model { id: 1, key_name: "blogDomain".into(), value: "blog.example.com".into() }];
+    let cat = |name: &str| category::Model {
+        id: 1, name: name.into(), introduce: None, path_name: None, icon: None, color: None, parent_id: None, sort_order: 0,
+    };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([settings.clone()])
+        .append_query_results([vec![(note_model(1, "Pinning", true), Some(cat("Rust")))]])
+        .append_query_results([Vec::<saudade_blog::entity::note_tag::Model>::new()])
+        .append_query_results([Vec::<tag_one::Model>::new()])
+        .append_query_results([Vec::<(tag_two::Model, Option<tag_one::Model>)>::new()])
+        .append_query_results([settings])
+        .append_query_results([vec![(note_model(1, "Pinning", true), Some(cat("Rust 语言")))]])
+        .append_query_results([Vec::<saudade_blog::entity::note_tag::Model>::new()])
+        .append_query_results([Vec::<tag_one::Model>::new()])
+        .append_query_results([Vec::<(tag_two::Model, Option<tag_one::Model>)>::new()])
+        .into_connection();
+    let app = create_router(AppState::new(db));
+
+    let first = app.clone()
+        .oneshot(Request::builder().uri("/feed.json").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(first.status(), StatusCode::OK);
+    let etag = first.headers().get("etag").unwrap().clone();
+
+    // Same note, same updated_at; only the category name changed
+    let second = app
+        .oneshot(Request::builder().uri("/feed.json").header("if-none-match", etag.clone()).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(second.status(), StatusCode::OK);
+    assert_ne!(second.headers().get("etag").unwrap(), &etag);
+}
+
+#[tokio::test]
+async fn test_create_note_rejects_invalid_publish_at() {
+    let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+
+    let response = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/protected/notes")
+                .header("authorization", "mock-token-for-admin")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"title":"Later","status":"published","publishAt":"next tuesday"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["message"], "Invalid publishAt");
+
+    // Nothing was written
+    let state = std::sync::Arc::try_unwrap(state).ok().unwrap();
+    assert!(state.db.into_transaction_log().is_empty());
+}
+
+#[tokio::test]
+async fn test_update_note_writes_in_one_transaction() {
+    use saudade_blog::entity::{note, note_revision, note_tag};
+    use sea_orm::MockExecResult;
+
+    let n = note::Model { slug: Some("pinning".into()), ..note_model(1, "Pinning", true) };
+    let rev = note_revision::Model {
+        id: 1, note_id: 1, title: "Pinning".into(), content: "body".into(), description: None, cover: None,
+        is_top: None, status: None, tags: Some(String::new()), category_id: None,
+        note_updated_at: n.updated_at, created_at: n.updated_at,
+    };
+    // The revision is written but the note update itself fails: no exec result is left for it
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![n]])
+        .append_query_results([Vec::<note_tag::Model>::new()])
+        .append_query_results([vec![rev]])
+        .append_exec_results([MockExecResult { last_insert_id: 1, rows_affected: 1 }])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+
+    let response = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/protected/notes/1")
+                .header("authorization", "mock-token-for-admin")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"title":"Pinning, revised"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_ne!(body["code"], 200);
+
+    let state = std::sync::Arc::try_unwrap(state).ok().unwrap();
+    let log = state.db.into_transaction_log();
+    // Lookup, then one transaction holding the revision and the failed update
+    assert_eq!(log.len(), 2);
+    let txn = format!("{:?}", log[1]);
+    assert!(txn.contains("INSERT INTO `note_revision`"));
+    assert!(txn.contains("UPDATE `note`"));
+}
+
+#[tokio::test]
+async fn test_restore_revision_publishes_note_updated() {
+    use saudade_blog::entity::{note, note_revision, note_tag, tag_one, tag_two};
+    use saudade_blog::events::{self, DomainEvent};
+    use sea_orm::MockExecResult;
+    use std::sync::Arc;
+
+    let n = note::Model { slug: Some("pinning".into()), ..note_model(1, "Pinning, revised", true) };
+    let rev = note_revision::Model {
+        id: 4, note_id: 1, title: "Pinning".into(), content: "body".into(), description: None, cover: None,
+        is_top: None, status: None, tags: Some(String::new()), category_id: None,
+        note_updated_at: n.updated_at, created_at: n.updated_at,
+    };
+    let exec = MockExecResult { last_insert_id: 5, rows_affected: 1 };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![rev.clone()]])
+        .append_query_results([vec![n.clone()]])
+        // In the transaction: the snapshot, the tag rewrite, then the note itself
+        .append_query_results([Vec::<note_tag::Model>::new()])
+        .append_query_results([vec![note_revision::Model { id: 5, ..rev }]])
+        .append_query_results([Vec::<tag_one::Model>::new()])
+        .append_query_results([Vec::<tag_two::Model>::new()])
+        .append_query_results([vec![note::Model { title: "Pinning".into(), ..n }]])
+        .append_exec_results([exec.clone(), exec.clone(), exec])
+        .into_connection();
+    let state = Arc::new(AppState::new(db));
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    state.events.subscribe("recorder", move |_, event: Arc<DomainEvent>| {
+        let tx = tx.clone();
+        async move {
+            if let DomainEvent::NoteUpdated { note, was_visible } = event.as_ref() {
+                tx.send((note.title.clone(), *was_visible)).unwrap();
+            }
+        }
+    });
+    events::spawn(state.clone());
+
+    let response = create_router(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/protected/notes/1/revisions/4/restore")
+                .header("authorization", "mock-token-for-admin")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["code"], 200, "{}", body);
+
+    assert_eq!(rx.recv().await, Some(("Pinning".to_string(), true)));
+}
+
+#[tokio::test]
+async fn test_old_slug_redirect_only_for_public_notes() {
+    use saudade_blog::entity::{category, note, note_slug_redirect};
+
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([Vec::<(note::Model, Option<category::Model>)>::new()])
+        .append_query_results([Vec::<(note_slug_redirect::Model, Option<note::Model>)>::new()])
+        .into_connection();
+    let state = std::sync::Arc::new(AppState::new(db));
+
+    let response = create_router(state.clone())
+        .oneshot(Request::builder().uri("/api/public/notes/by-slug/old-draft").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert!(body["data"].is_null());
+
+    let state = std::sync::Arc::try_unwrap(state).ok().unwrap();
+    let log = state.db.into_transaction_log();
+    let lookup = format!("{:?}", log[1]);
+    assert!(lookup.contains("FROM `note_slug_redirect`"));
+    assert!(lookup.contains("`note`.`is_public` = ?"));
+    assert!(lookup.contains("`note`.`status` NOT IN (?, ?)"));
+}